@@ -0,0 +1,274 @@
+use libsoda_sys::soda_response::SodaMessageType;
+use libsoda_sys::{ExtendedSodaConfigMsg, SodaConfig, SodaHandle, SodaResponse, SodaResultHandler};
+use prost::Message;
+use std::ffi::{c_char, c_int, c_void};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Name of the script file in the language pack directory.
+const SCRIPT_FILE_NAME: &str = "fake_soda_script.bin";
+
+/// Name of the file the received config is saved to.
+const CONFIG_FILE_NAME: &str = "fake_soda_config.bin";
+
+/// Audio time of the responses sent when the audio is marked as done.
+const AT_MARK_DONE: u64 = u64::MAX;
+
+/// Audio time of the responses sent by another thread `at_audio_ms - AFTER_MARK_DONE`
+/// milliseconds after the audio is marked as done, like libsoda still decoding the audio.
+/// They are followed by the responses at `AT_MARK_DONE`.
+const AFTER_MARK_DONE: u64 = u64::MAX / 2;
+
+struct ScriptedResponse {
+    at_audio_ms: u64,
+    response: Vec<u8>,
+}
+
+struct FakeSoda {
+    callback: Option<SodaResultHandler>,
+    callback_handle: *const c_void,
+
+    channel_count: u64,
+    sample_rate: u64,
+
+    script: Vec<ScriptedResponse>,
+    next_response: usize,
+    bytes_received: u64,
+
+    /// Sends the delayed responses after mark done.
+    decoding_thread: Option<JoinHandle<()>>,
+
+    /// Set to drop the responses not sent by the decoding thread yet.
+    is_decoding_cancelled: Arc<AtomicBool>,
+}
+
+/// The callback passed to the thread sending the delayed responses.
+struct Callback {
+    callback: SodaResultHandler,
+    callback_handle: *const c_void,
+}
+
+// libsoda calls the callback from its own threads as well
+unsafe impl Send for Callback {}
+
+impl Callback {
+    fn send(&self, response: &[u8]) {
+        unsafe {
+            (self.callback)(
+                response.as_ptr() as *const c_char,
+                response.len() as c_int,
+                self.callback_handle,
+            );
+        }
+    }
+}
+
+impl FakeSoda {
+    fn send(&self, response: &[u8]) {
+        if let Some(callback) = self.callback {
+            unsafe {
+                callback(
+                    response.as_ptr() as *const c_char,
+                    response.len() as c_int,
+                    self.callback_handle,
+                );
+            }
+        }
+    }
+
+    fn send_message_type(&self, soda_type: SodaMessageType) {
+        let response = SodaResponse {
+            soda_type: Some(soda_type as i32),
+            ..SodaResponse::default()
+        };
+        self.send(&response.encode_to_vec());
+    }
+
+    /// Sends the scripted responses up to the given audio time.
+    fn send_script(&mut self, audio_ms: u64) {
+        while let Some(scripted) = self.script.get(self.next_response) {
+            if scripted.at_audio_ms > audio_ms {
+                break;
+            }
+            self.next_response += 1;
+            self.send(&scripted.response);
+        }
+    }
+
+    /// Sends the rest of the script from another thread, waiting for the delayed responses.
+    fn send_script_later(&mut self) {
+        let callback = match self.callback {
+            Some(callback) => Callback {
+                callback,
+                callback_handle: self.callback_handle,
+            },
+            None => return,
+        };
+        let responses = self.script[self.next_response..]
+            .iter()
+            .map(|scripted| (scripted.at_audio_ms, scripted.response.clone()))
+            .collect::<Vec<_>>();
+        self.next_response = self.script.len();
+
+        let is_cancelled = Arc::new(AtomicBool::new(false));
+        self.is_decoding_cancelled = is_cancelled.clone();
+        self.decoding_thread = Some(thread::spawn(move || {
+            let callback = callback;
+            let mut elapsed_ms = 0;
+            for (at_audio_ms, response) in responses {
+                if at_audio_ms < AT_MARK_DONE {
+                    let delay_ms = at_audio_ms - AFTER_MARK_DONE;
+                    thread::sleep(Duration::from_millis(delay_ms.saturating_sub(elapsed_ms)));
+                    elapsed_ms = elapsed_ms.max(delay_ms);
+                }
+                if is_cancelled.load(Ordering::SeqCst) {
+                    return;
+                }
+                callback.send(&response);
+            }
+        }));
+    }
+
+    /// Drops the responses of the audio not decoded yet, like stopping libsoda does.
+    fn cancel_decoding(&mut self) {
+        self.is_decoding_cancelled.store(true, Ordering::SeqCst);
+        if let Some(decoding_thread) = self.decoding_thread.take() {
+            let _ = decoding_thread.join();
+        }
+    }
+
+    fn audio_ms(&self) -> u64 {
+        // 16 bit samples
+        self.bytes_received * 1000u64 / (2u64 * self.channel_count * self.sample_rate)
+    }
+}
+
+fn read_script(path: PathBuf) -> Vec<ScriptedResponse> {
+    let data = fs::read(path).unwrap_or_default();
+    let mut script = Vec::new();
+
+    let mut pos = 0;
+    while pos + 12 <= data.len() {
+        let at_audio_ms = u64::from_le_bytes(data[pos..pos + 8].try_into().unwrap());
+        let len = u32::from_le_bytes(data[pos + 8..pos + 12].try_into().unwrap()) as usize;
+        pos += 12;
+
+        let end = (pos + len).min(data.len());
+        script.push(ScriptedResponse {
+            at_audio_ms,
+            response: data[pos..end].to_vec(),
+        });
+        pos = end;
+    }
+
+    // the responses with the same time keep their order
+    script.sort_by_key(|scripted| scripted.at_audio_ms);
+    script
+}
+
+/// # Safety
+///
+/// `config.soda_config` must point to `config.soda_config_size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn CreateExtendedSodaAsync(config: SodaConfig) -> SodaHandle {
+    let config_buf = std::slice::from_raw_parts(
+        config.soda_config as *const u8,
+        config.soda_config_size.max(0) as usize,
+    );
+    let soda_config = match ExtendedSodaConfigMsg::decode(config_buf) {
+        Ok(soda_config) => soda_config,
+        Err(_) => return std::ptr::null_mut(),
+    };
+
+    let folder = PathBuf::from(soda_config.language_pack_directory());
+    let _ = fs::write(folder.join(CONFIG_FILE_NAME), config_buf);
+
+    let fake_soda = FakeSoda {
+        callback: config.callback,
+        callback_handle: config.callback_handle,
+        channel_count: soda_config.channel_count.unwrap_or(1).max(1) as u64,
+        sample_rate: soda_config.sample_rate.unwrap_or(16000).max(1) as u64,
+        script: read_script(folder.join(SCRIPT_FILE_NAME)),
+        next_response: 0,
+        bytes_received: 0,
+        decoding_thread: None,
+        is_decoding_cancelled: Arc::new(AtomicBool::new(false)),
+    };
+
+    Box::into_raw(Box::new(fake_soda)) as SodaHandle
+}
+
+/// # Safety
+///
+/// `handle` must be created by `CreateExtendedSodaAsync` and not deleted yet.
+#[no_mangle]
+pub unsafe extern "C" fn DeleteExtendedSodaAsync(handle: SodaHandle) {
+    if !handle.is_null() {
+        let mut fake_soda = Box::from_raw(handle as *mut FakeSoda);
+        fake_soda.cancel_decoding();
+    }
+}
+
+/// # Safety
+///
+/// `handle` must be created by `CreateExtendedSodaAsync` and not deleted yet.
+#[no_mangle]
+pub unsafe extern "C" fn ExtendedSodaStart(handle: SodaHandle) {
+    if let Some(fake_soda) = (handle as *mut FakeSoda).as_mut() {
+        fake_soda.cancel_decoding();
+        fake_soda.next_response = 0;
+        fake_soda.bytes_received = 0;
+        fake_soda.send_message_type(SodaMessageType::Start);
+        fake_soda.send_script(0);
+    }
+}
+
+/// # Safety
+///
+/// `handle` must be created by `CreateExtendedSodaAsync` and not deleted yet.
+#[no_mangle]
+pub unsafe extern "C" fn ExtendedSodaStop(handle: SodaHandle) {
+    if let Some(fake_soda) = (handle as *mut FakeSoda).as_mut() {
+        fake_soda.cancel_decoding();
+        fake_soda.send_message_type(SodaMessageType::Stop);
+    }
+}
+
+/// # Safety
+///
+/// `handle` must be created by `CreateExtendedSodaAsync` and not deleted yet.
+#[no_mangle]
+pub unsafe extern "C" fn ExtendedSodaMarkDone(handle: SodaHandle) {
+    if let Some(fake_soda) = (handle as *mut FakeSoda).as_mut() {
+        let is_decoding = fake_soda.script[fake_soda.next_response..]
+            .iter()
+            .any(|scripted| (AFTER_MARK_DONE..AT_MARK_DONE).contains(&scripted.at_audio_ms));
+        if is_decoding {
+            fake_soda.send_script_later();
+        } else {
+            fake_soda.send_script(AT_MARK_DONE);
+        }
+    }
+}
+
+/// # Safety
+///
+/// `handle` must be created by `CreateExtendedSodaAsync` and not deleted yet.
+/// `buffer` must point to `buffer_length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ExtendedAddAudio(
+    handle: SodaHandle,
+    _buffer: *const c_char,
+    buffer_length: c_int,
+) {
+    if let Some(fake_soda) = (handle as *mut FakeSoda).as_mut() {
+        fake_soda.bytes_received += buffer_length.max(0) as u64;
+        let audio_ms = fake_soda.audio_ms();
+        // responses at the end of audio are sent only by mark done
+        fake_soda.send_script(audio_ms.min(AFTER_MARK_DONE - 1));
+    }
+}
@@ -2,6 +2,9 @@ use libloading::{library_filename, Library, Symbol};
 use std::ffi::{c_char, c_int, c_void};
 use std::path::PathBuf;
 
+mod soda_session;
+pub use soda_session::SodaSession;
+
 include!(concat!(env!("OUT_DIR"), "/speech.soda.chrome.rs"));
 
 /// The callback that gets executed on a SODA event. It takes in a
@@ -55,22 +58,28 @@ impl LibSoda {
             let library = Library::new(path.into().join(library_filename("soda")))?;
 
             let func: Symbol<CreateSodaAsync> = library.get(b"CreateExtendedSodaAsync\0")?;
-            let create_soda_async = std::mem::transmute(func.into_raw().into_raw());
+            let create_soda_async =
+                std::mem::transmute::<*mut c_void, CreateSodaAsync>(func.into_raw().into_raw());
 
             let func: Symbol<DeleteSodaAsync> = library.get(b"DeleteExtendedSodaAsync\0")?;
-            let delete_soda_async = std::mem::transmute(func.into_raw().into_raw());
+            let delete_soda_async =
+                std::mem::transmute::<*mut c_void, DeleteSodaAsync>(func.into_raw().into_raw());
 
             let func: Symbol<SodaStart> = library.get(b"ExtendedSodaStart\0")?;
-            let soda_start = std::mem::transmute(func.into_raw().into_raw());
+            let soda_start =
+                std::mem::transmute::<*mut c_void, SodaStart>(func.into_raw().into_raw());
 
             let func: Symbol<SodaStop> = library.get(b"ExtendedSodaStop\0")?;
-            let soda_stop = std::mem::transmute(func.into_raw().into_raw());
+            let soda_stop =
+                std::mem::transmute::<*mut c_void, SodaStop>(func.into_raw().into_raw());
 
             let func: Symbol<AddAudio> = library.get(b"ExtendedAddAudio\0")?;
-            let add_audio = std::mem::transmute(func.into_raw().into_raw());
+            let add_audio =
+                std::mem::transmute::<*mut c_void, AddAudio>(func.into_raw().into_raw());
 
             let func: Symbol<SodaMarkDone> = library.get(b"ExtendedSodaMarkDone\0")?;
-            let mark_done = std::mem::transmute(func.into_raw().into_raw());
+            let mark_done =
+                std::mem::transmute::<*mut c_void, SodaMarkDone>(func.into_raw().into_raw());
 
             Ok(Self {
                 _library: library,
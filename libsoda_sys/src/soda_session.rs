@@ -0,0 +1,114 @@
+use crate::{ExtendedSodaConfigMsg, LibSoda, SodaConfig, SodaHandle, SodaResponse};
+use prost::Message;
+use std::ffi::{c_char, c_int, c_void};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+
+type Callback = Box<dyn Fn(SodaResponse) + Send + Sync>;
+
+/// Safe wrapper of the libsoda handle.
+///
+/// The callback is executed on the libsoda thread with the decoded responses.
+/// Responses that cannot be decoded are skipped and panics are caught,
+/// so they don't unwind into libsoda.
+pub struct SodaSession {
+    lib_soda: Arc<LibSoda>,
+    handle: SodaHandle,
+
+    /// Passed to libsoda as `callback_handle`, freed after the handle is deleted.
+    callback: *mut Callback,
+}
+
+// libsoda handles are not bound to the thread they are created on
+unsafe impl Send for SodaSession {}
+
+impl SodaSession {
+    /// Returns `None` if libsoda cannot create the session.
+    pub fn new<F>(
+        lib_soda: Arc<LibSoda>,
+        config: &ExtendedSodaConfigMsg,
+        callback: F,
+    ) -> Option<Self>
+    where
+        F: Fn(SodaResponse) + Send + Sync + 'static,
+    {
+        let config_buf = config.encode_to_vec();
+        let callback: *mut Callback = Box::into_raw(Box::new(Box::new(callback)));
+
+        let soda_config = SodaConfig {
+            soda_config: config_buf.as_ptr() as *const c_char,
+            soda_config_size: config_buf.len() as c_int,
+            callback: Some(session_callback),
+            callback_handle: callback as *const c_void,
+        };
+
+        let handle = unsafe { (lib_soda.create_soda_async)(soda_config) };
+        if handle.is_null() {
+            unsafe {
+                drop(Box::from_raw(callback));
+            }
+            return None;
+        }
+
+        Some(Self {
+            lib_soda,
+            handle,
+            callback,
+        })
+    }
+
+    pub fn start(&self) {
+        unsafe { (self.lib_soda.soda_start)(self.handle) }
+    }
+
+    pub fn stop(&self) {
+        unsafe { (self.lib_soda.soda_stop)(self.handle) }
+    }
+
+    /// Adds mono 16 bit audio in the sample rate of the config.
+    pub fn add_audio(&self, samples: &[i16]) {
+        unsafe {
+            (self.lib_soda.add_audio)(
+                self.handle,
+                samples.as_ptr() as *const c_char,
+                std::mem::size_of_val(samples) as c_int,
+            )
+        }
+    }
+
+    /// Tells libsoda that no more audio will be added,
+    /// so it can report the final result of the buffered audio.
+    pub fn mark_done(&self) {
+        unsafe { (self.lib_soda.mark_done)(self.handle) }
+    }
+}
+
+impl Drop for SodaSession {
+    fn drop(&mut self) {
+        unsafe {
+            // libsoda may call the callback until the handle is deleted
+            (self.lib_soda.delete_soda_async)(self.handle);
+            drop(Box::from_raw(self.callback));
+        }
+    }
+}
+
+unsafe extern "C" fn session_callback(
+    response: *const c_char,
+    res_length: c_int,
+    callback_handle: *const c_void,
+) {
+    if response.is_null() || callback_handle.is_null() {
+        return;
+    }
+
+    let response = std::slice::from_raw_parts(response as *const u8, res_length.max(0) as usize);
+    let callback = &*(callback_handle as *const Callback);
+
+    // unwinding into C code is undefined behavior
+    let _ = catch_unwind(AssertUnwindSafe(|| {
+        if let Ok(response) = SodaResponse::decode(response) {
+            callback(response);
+        }
+    }));
+}
@@ -1,193 +1,414 @@
+use crate::language_packs::find_language_pack;
 use async_trait::async_trait;
-use futures::channel::mpsc;
-use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender};
+use futures::channel::oneshot;
 use libsoda_sys::extended_soda_config_msg::RecognitionMode;
 use libsoda_sys::soda_endpoint_event::EndpointType;
-use libsoda_sys::soda_recognition_result::ResultType;
+use libsoda_sys::soda_recognition_result::{FinalResultEndpointReason, ResultType};
 use libsoda_sys::soda_response::SodaMessageType;
-use libsoda_sys::{ExtendedSodaConfigMsg, LibSoda, SodaConfig, SodaHandle, SodaResponse};
+use libsoda_sys::{
+    ExtendedSodaConfigMsg, HypothesisPart, LibSoda, MultilangConfig, SodaResponse, SodaSession,
+};
 use marek_speech_recognition_api::{
-    RecognitionEvent, Recognizer, RecognizerInfo, RecognizerOptions, SpeechError, SpeechResult,
+    event_channel, Alternative, AudioBuffer, AudioConverter, AudioFormat, AudioLevelMeter,
+    CommandMatcher, EndpointReason, EventChannelOptions, EventFilter, EventReceiver, EventSender,
+    RecognitionEvent, Recognizer, RecognizerInfo, RecognizerOptions, RecognizerState, SpeechError,
+    SpeechResult, Word,
 };
-use prost::Message;
-use std::ffi::{c_char, c_int, c_void};
-use std::fs;
+use std::collections::HashMap;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tokio::time::sleep;
+use tokio::time::{sleep, timeout};
+
+/// How long `stop` waits in batch mode for the next response after marking the end of audio.
+/// libsoda keeps responding (audio levels, results) while it decodes the buffered audio,
+/// so `stop` waits as long as the decoding goes on, however long the written audio is.
+const BATCH_STOP_IDLE_TIMEOUT_MS: u64 = 2000;
 
 pub struct GoogleRecognizer {
-    recognizer_options: RecognizerOptions,
     info: RecognizerInfo,
+    converter: AudioConverter,
     lib_soda: Arc<LibSoda>,
-    sender: *mut UnboundedSender<RecognitionEvent>,
-    handle: SodaHandle,
+    config: ExtendedSodaConfigMsg,
+    session: SodaSession,
+    callback_context: Arc<CallbackContext>,
+    state: RecognizerState,
+    batch_mode: bool,
     start_time: Instant,
     samples_written: usize,
 }
 
-unsafe impl Send for GoogleRecognizer {}
-
-#[no_mangle]
-pub extern "C" fn callback(
-    response: *const c_char,
-    res_length: c_int,
-    callback_handle: *const c_void,
-) {
-    let slice = unsafe { std::slice::from_raw_parts(response as *const u8, res_length as usize) };
-
-    if let Ok(result) = SodaResponse::decode(slice) {
-        let event = if result.soda_type() == SodaMessageType::Start {
-            Some(RecognitionEvent::Start)
-        } else if result.soda_type() == SodaMessageType::Stop {
-            Some(RecognitionEvent::Stop)
-        } else if let Some(endpoint_event) = &result.endpoint_event {
-            let audio_time_usec = if let Some(timing_metrics) = &endpoint_event.timing_metrics {
-                timing_metrics.event_end_time_usec.map(|time| time as u64)
-            } else {
-                None
-            };
+/// Data used by the session callback.
+struct CallbackContext {
+    sender: EventSender,
+    max_alternatives: usize,
 
-            match endpoint_event.endpoint_type() {
-                EndpointType::StartOfSpeech => {
-                    Some(RecognitionEvent::StartOfSpeech { audio_time_usec })
-                }
-                EndpointType::EndOfSpeech => {
-                    Some(RecognitionEvent::EndOfSpeech { audio_time_usec })
-                }
-                _ => None,
+    /// Set in the commands mode.
+    command_matcher: Option<CommandMatcher>,
+
+    /// Notified when libsoda reports the final result of the audio marked as done.
+    end_of_audio_sender: Mutex<Option<oneshot::Sender<()>>>,
+
+    /// Time of the last libsoda response, to tell whether it still decodes the audio.
+    last_response_time: Mutex<Instant>,
+
+    /// Set by `abort`, the responses of the aborted session are ignored.
+    is_aborted: AtomicBool,
+}
+
+/// Converts libsoda responses to events.
+fn on_response(context: &CallbackContext, result: SodaResponse) {
+    if context.is_aborted.load(Ordering::SeqCst) {
+        return;
+    }
+    *context.last_response_time.lock().unwrap() = Instant::now();
+
+    let event = if result.soda_type() == SodaMessageType::Start {
+        Some(RecognitionEvent::Start)
+    } else if result.soda_type() == SodaMessageType::Stop {
+        Some(RecognitionEvent::Stop)
+    } else if let Some(endpoint_event) = &result.endpoint_event {
+        let audio_time_usec = if let Some(timing_metrics) = &endpoint_event.timing_metrics {
+            timing_metrics.event_end_time_usec.map(|time| time as u64)
+        } else {
+            None
+        };
+
+        match endpoint_event.endpoint_type() {
+            EndpointType::StartOfSpeech => {
+                Some(RecognitionEvent::StartOfSpeech { audio_time_usec })
             }
-        } else if let Some(mut recognition_event) = result.recognition_result {
-            let audio_time_usec = if let Some(timing_metrics) = &recognition_event.timing_metrics {
-                timing_metrics.event_end_time_usec.map(|time| time as u64)
+            EndpointType::EndOfSpeech => Some(RecognitionEvent::EndOfSpeech { audio_time_usec }),
+            EndpointType::EndOfUtterance => {
+                Some(RecognitionEvent::EndOfUtterance { audio_time_usec })
+            }
+            EndpointType::EndOfAudio => Some(RecognitionEvent::EndOfAudio { audio_time_usec }),
+            _ => None,
+        }
+    } else if let Some(recognition_event) = result.recognition_result {
+        let (audio_start_time_usec, audio_time_usec) =
+            if let Some(timing_metrics) = &recognition_event.timing_metrics {
+                (
+                    timing_metrics.audio_start_time_usec.map(|time| time as u64),
+                    timing_metrics.event_end_time_usec.map(|time| time as u64),
+                )
             } else {
-                None
+                (None, None)
             };
+        let words = to_words(
+            &recognition_event.hypothesis_part,
+            audio_start_time_usec,
+            audio_time_usec,
+        );
+        let is_final = recognition_event.result_type() == ResultType::Final;
+        let endpoint_reason = if is_final {
+            to_endpoint_reason(recognition_event.endpoint_reason())
+        } else {
+            None
+        };
 
-            Some(RecognitionEvent::Recognition {
-                text: if !recognition_event.hypothesis.is_empty() {
-                    recognition_event.hypothesis.swap_remove(0)
-                } else {
-                    "".to_string()
-                },
-                is_final: recognition_event.result_type() == ResultType::Final,
+        // SODA doesn't provide the probability, only the order of hypotheses
+        // and the words only for the first one
+        let alternatives = recognition_event
+            .hypothesis
+            .iter()
+            .take(context.max_alternatives)
+            .enumerate()
+            .map(|(i, hypothesis)| Alternative {
+                text: hypothesis.clone(),
+                confidence: None,
+                words: if i == 0 { words.clone() } else { None },
+            })
+            .collect();
 
-                audio_start_time_usec: None,
-                audio_end_time_usec: audio_time_usec,
+        Some(RecognitionEvent::Recognition {
+            text: recognition_event
+                .hypothesis
+                .into_iter()
+                .next()
+                .unwrap_or_default(),
+            is_final,
 
-                words: None,
+            audio_start_time_usec,
+            audio_end_time_usec: audio_time_usec,
+
+            words,
+            endpoint_reason,
+            alternatives,
+            speaker_vector: None,
+        })
+    } else if let Some(audio_level_info) = result.audio_level_info {
+        let rms = audio_level_info.rms.unwrap_or_default();
+        Some(RecognitionEvent::AudioLevel {
+            rms,
+            // SODA's audio level is the speech likelihood, the loudness is computed
+            // the same way as for the other backends
+            level: AudioLevelMeter::level_of(rms),
+            speech_likelihood: audio_level_info.audio_level,
+            audio_time_usec: audio_level_info.audio_time_usec.map(|time| time as u64),
+        })
+    } else if let Some(langid_event) = result.langid_event {
+        let confidence_level = langid_event.confidence_level;
+        langid_event
+            .language
+            .map(|language| RecognitionEvent::Language {
+                id: language,
+                confidence_level,
             })
-        } else if let Some(langid_event) = result.langid_event {
-            langid_event
-                .language
-                .map(|language| RecognitionEvent::Language { id: language })
-        } else {
-            None
+    } else {
+        None
+    };
+
+    if let Some(event) = event {
+        // SODA doesn't provide the confidence
+        let command_event = match (&event, &context.command_matcher) {
+            (
+                RecognitionEvent::Recognition {
+                    text,
+                    is_final: true,
+                    ..
+                },
+                Some(command_matcher),
+            ) if !text.is_empty() => Some(command_matcher.process(text, 1.0f32)),
+            _ => None,
         };
 
-        if let Some(event) = event {
-            let sender = callback_handle as *mut UnboundedSender<RecognitionEvent>;
-            unsafe {
-                sender.as_mut().unwrap().unbounded_send(event).unwrap();
+        let is_end_of_audio = matches!(
+            event,
+            RecognitionEvent::Stop
+                | RecognitionEvent::Recognition {
+                    endpoint_reason: Some(EndpointReason::EndOfAudio),
+                    ..
+                }
+        );
+
+        let _ = context.sender.send(event);
+        if let Some(command_event) = command_event {
+            let _ = context.sender.send(command_event);
+        }
+
+        if is_end_of_audio {
+            if let Some(sender) = context.end_of_audio_sender.lock().unwrap().take() {
+                let _ = sender.send(());
             }
         }
     }
 }
 
+/// Converts parts of the first hypothesis to words. Each part starts at its alignment
+/// and ends where the next one starts.
+fn to_words(
+    hypothesis_parts: &[HypothesisPart],
+    audio_start_time_usec: Option<u64>,
+    audio_end_time_usec: Option<u64>,
+) -> Option<Vec<Word>> {
+    let audio_start_time_usec = audio_start_time_usec?;
+    if hypothesis_parts.is_empty() {
+        return None;
+    }
+
+    let start_times = hypothesis_parts
+        .iter()
+        .map(|part| {
+            audio_start_time_usec + part.alignment_ms.unwrap_or_default().max(0) as u64 * 1000u64
+        })
+        .collect::<Vec<_>>();
+
+    Some(
+        hypothesis_parts
+            .iter()
+            .enumerate()
+            .map(|(i, part)| {
+                let start_time_usec = start_times[i];
+                let end_time_usec = start_times
+                    .get(i + 1)
+                    .copied()
+                    .or(audio_end_time_usec)
+                    .unwrap_or(start_time_usec)
+                    .max(start_time_usec);
+
+                Word {
+                    // SODA doesn't provide confidence of words
+                    conf: 1.0f32,
+                    start_time_usec,
+                    end_time_usec,
+                    // the first text is formatted, the second one (if present) is raw
+                    word: part.text.first().cloned().unwrap_or_default(),
+                }
+            })
+            .collect(),
+    )
+}
+
+fn to_endpoint_reason(reason: FinalResultEndpointReason) -> Option<EndpointReason> {
+    match reason {
+        FinalResultEndpointReason::EndpointEndOfSpeech => Some(EndpointReason::EndOfSpeech),
+        FinalResultEndpointReason::EndpointEndOfUtterance => Some(EndpointReason::EndOfUtterance),
+        FinalResultEndpointReason::EndpointEndOfAudio => Some(EndpointReason::EndOfAudio),
+        FinalResultEndpointReason::EndpointAsrResetByHotword => Some(EndpointReason::Hotword),
+        FinalResultEndpointReason::EndpointAsrResetExternal => Some(EndpointReason::Reset),
+        FinalResultEndpointReason::EndpointAsrError => Some(EndpointReason::Error),
+        FinalResultEndpointReason::EndpointUnknown => None,
+    }
+}
+
 impl GoogleRecognizer {
     pub(crate) fn new(
         lib_soda: Arc<LibSoda>,
         language_packs_folder: &Path,
         recognizer_options: RecognizerOptions,
-    ) -> SpeechResult<(Self, UnboundedReceiver<RecognitionEvent>)> {
+        batch_mode: bool,
+    ) -> SpeechResult<(Self, EventReceiver)> {
         let folder =
             Self::get_language_pack_folder(language_packs_folder, &recognizer_options.language)?;
 
-        unsafe {
-            let config = ExtendedSodaConfigMsg {
-                channel_count: Some(1),
-                sample_rate: Some(recognizer_options.sample_rate),
-                api_key: Some("dummy_api_key".to_string()),
-                language_pack_directory: Some(folder),
-                recognition_mode: Some(match recognizer_options.mode {
-                    marek_speech_recognition_api::RecognitionMode::Commands(_) => {
-                        RecognitionMode::Ime
-                    }
-                    marek_speech_recognition_api::RecognitionMode::Speech => {
-                        RecognitionMode::Caption
-                    }
-                    _ => RecognitionMode::Ime,
-                } as i32),
-                include_timing_metrics: Some(true),
-                ..ExtendedSodaConfigMsg::default()
-            };
-            let config_buf = config.encode_to_vec();
+        // libsoda switches between the language packs when other language is detected
+        let mut multilang_language_pack_directory = HashMap::new();
+        for language in &recognizer_options.alternative_languages {
+            if *language != recognizer_options.language {
+                multilang_language_pack_directory.insert(
+                    language.clone(),
+                    Self::get_language_pack_folder(language_packs_folder, language)?,
+                );
+            }
+        }
+        let enable_lang_id = !multilang_language_pack_directory.is_empty();
 
-            let (sender, receiver) = mpsc::unbounded();
-            let sender = Box::new(sender);
-            let sender = Box::into_raw(sender);
+        // libsoda accepts mono audio in any sample rate
+        let audio_format = recognizer_options.audio_format;
+        let converter = AudioConverter::new(
+            audio_format,
+            AudioFormat::mono_i16(audio_format.sample_rate),
+        )?;
 
-            let soda_config = SodaConfig {
-                soda_config: config_buf.as_ptr() as *const c_char,
-                soda_config_size: config_buf.len() as c_int,
-                callback: Some(callback),
-                callback_handle: sender as *const c_void,
-            };
+        let config = ExtendedSodaConfigMsg {
+            channel_count: Some(1),
+            sample_rate: Some(converter.output_format().sample_rate as i32),
+            api_key: Some("dummy_api_key".to_string()),
+            language_pack_directory: Some(folder),
+            recognition_mode: Some(match recognizer_options.mode {
+                marek_speech_recognition_api::RecognitionMode::Commands(_) => RecognitionMode::Ime,
+                marek_speech_recognition_api::RecognitionMode::Speech => RecognitionMode::Caption,
+                _ => RecognitionMode::Ime,
+            } as i32),
+            include_timing_metrics: Some(true),
+            // with `true` libsoda would pace the audio in real time also in batch mode
+            simulate_realtime_testonly: Some(false),
+            enable_lang_id: Some(enable_lang_id),
+            multilang_config: if enable_lang_id {
+                Some(MultilangConfig {
+                    multilang_language_pack_directory,
+                    rewind_when_switching_language: Some(true),
+                })
+            } else {
+                None
+            },
+            ..ExtendedSodaConfigMsg::default()
+        };
+
+        let (sender, receiver) = event_channel(recognizer_options.event_channel);
+        let callback_context = Arc::new(CallbackContext {
+            sender,
+            max_alternatives: recognizer_options.max_alternatives as usize,
+            command_matcher: match &recognizer_options.mode {
+                marek_speech_recognition_api::RecognitionMode::Commands(commands) => {
+                    Some(CommandMatcher::new(
+                        commands.clone(),
+                        recognizer_options.command_confidence_threshold,
+                    ))
+                }
+                _ => None,
+            },
+            end_of_audio_sender: Mutex::new(None),
+            last_response_time: Mutex::new(Instant::now()),
+            is_aborted: AtomicBool::new(false),
+        });
 
-            let handle = (lib_soda.create_soda_async)(soda_config);
-
-            Ok((
-                Self {
-                    recognizer_options,
-                    info: RecognizerInfo {
-                        name: "Google libsoda".to_string(),
-                        is_realtime_only: true,
-                        has_punctuation: true,
-                    },
-                    lib_soda,
-                    sender,
-                    handle,
-                    start_time: Instant::now(),
-                    samples_written: 0,
+        let session = Self::create_session(&lib_soda, &config, &callback_context)?;
+
+        Ok((
+            Self {
+                info: RecognizerInfo {
+                    name: "Google libsoda".to_string(),
+                    is_realtime_only: !batch_mode,
+                    has_punctuation: true,
+                    supports_command_updates: false,
                 },
-                receiver,
-            ))
+                converter,
+                lib_soda,
+                config,
+                session,
+                callback_context,
+                state: RecognizerState::Created,
+                batch_mode,
+                start_time: Instant::now(),
+                samples_written: 0,
+            },
+            receiver,
+        ))
+    }
+
+    fn create_session(
+        lib_soda: &Arc<LibSoda>,
+        config: &ExtendedSodaConfigMsg,
+        callback_context: &Arc<CallbackContext>,
+    ) -> SpeechResult<SodaSession> {
+        let callback_context = callback_context.clone();
+        SodaSession::new(lib_soda.clone(), config, move |response| {
+            on_response(&callback_context, response)
+        })
+        .ok_or_else(|| SpeechError::LoadLibraryError("Cannot create libsoda session".to_string()))
+    }
+
+    /// Marks the end of audio and waits until libsoda processes the buffered audio.
+    async fn wait_for_end_of_audio(&mut self) {
+        let (sender, mut receiver) = oneshot::channel();
+        self.callback_context
+            .end_of_audio_sender
+            .lock()
+            .unwrap()
+            .replace(sender);
+        *self.callback_context.last_response_time.lock().unwrap() = Instant::now();
+        self.session.mark_done();
+
+        // when libsoda goes quiet there is just no final result for the end of audio
+        // (e.g. silence), the wait is extended while the responses keep coming
+        loop {
+            let idle_time = self
+                .callback_context
+                .last_response_time
+                .lock()
+                .unwrap()
+                .elapsed();
+            let idle_timeout = Duration::from_millis(BATCH_STOP_IDLE_TIMEOUT_MS);
+            if idle_time >= idle_timeout {
+                break;
+            }
+            if timeout(idle_timeout - idle_time, &mut receiver)
+                .await
+                .is_ok()
+            {
+                break;
+            }
         }
+
+        self.callback_context
+            .end_of_audio_sender
+            .lock()
+            .unwrap()
+            .take();
     }
 
     fn get_language_pack_folder(
         language_packs_folder: &Path,
         language_name: &str,
     ) -> SpeechResult<String> {
-        let folder = language_packs_folder.join(language_name);
-        let versions =
-            fs::read_dir(&folder).map_err(|_| SpeechError::LanguageFolderError(folder.clone()))?;
-        let mut versions = versions
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .collect::<Vec<_>>();
-        versions.sort_by_key(|b| std::cmp::Reverse(b.file_name()));
-        let version = versions
-            .first()
-            .map(|v| v.file_name())
-            .ok_or_else(|| SpeechError::LanguageFolderError(folder.clone()))?;
-        let folder = folder.join(version).join("SODAModels");
-        let folder = folder
-            .as_path()
+        let folder = find_language_pack(language_packs_folder, language_name)?;
+        folder
             .to_str()
-            .ok_or_else(|| SpeechError::LanguageFolderError(folder.clone()))?
-            .to_string();
-        Ok(folder)
-    }
-}
-
-impl Drop for GoogleRecognizer {
-    fn drop(&mut self) {
-        unsafe {
-            (self.lib_soda.delete_soda_async)(self.handle);
-            let _ = Box::from_raw(self.sender);
-        }
+            .map(|folder| folder.to_string())
+            .ok_or(SpeechError::LanguageFolderError(folder))
     }
 }
 
@@ -197,37 +418,57 @@ impl Recognizer for GoogleRecognizer {
         &self.info
     }
 
+    fn state(&self) -> RecognizerState {
+        self.state
+    }
+
+    fn subscribe(&self, filter: EventFilter, options: EventChannelOptions) -> EventReceiver {
+        self.callback_context.sender.subscribe(filter, options)
+    }
+
     async fn start(&mut self) -> SpeechResult<()> {
-        self.samples_written = 0;
+        self.state.check_start()?;
 
-        unsafe {
-            (self.lib_soda.soda_start)(self.handle);
+        // libsoda doesn't guarantee that a stopped session can be started again
+        if self.state == RecognizerState::Stopped {
+            self.session =
+                Self::create_session(&self.lib_soda, &self.config, &self.callback_context)?;
+            self.callback_context
+                .is_aborted
+                .store(false, Ordering::SeqCst);
         }
 
+        self.samples_written = 0;
+        self.converter.reset();
+
+        self.session.start();
+        self.state = RecognizerState::Running;
+
         Ok(())
     }
 
-    async fn write(&mut self, buffer: &[i16]) -> SpeechResult {
+    async fn write(&mut self, buffer: AudioBuffer<'_>) -> SpeechResult {
+        self.state.check_write()?;
+        if self.state == RecognizerState::Paused {
+            return Ok(());
+        }
+
+        let buffer = self.converter.convert_i16(buffer)?;
+
         // google recognizer works in real time only
-        // simulate the delay between buffers
+        // simulate the delay between buffers (in batch mode the audio is fed without pacing)
         if self.samples_written == 0 {
             self.start_time = Instant::now();
-        } else {
+        } else if !self.batch_mode {
             let elapsed_ms = self.start_time.elapsed().as_millis() as u64;
             let dest_time_ms = (self.samples_written as u64 * 1000u64)
-                / (self.recognizer_options.sample_rate as u64);
+                / (self.converter.output_format().sample_rate as u64);
             if dest_time_ms > elapsed_ms + 2 {
                 sleep(Duration::from_millis(dest_time_ms - elapsed_ms)).await;
             }
         }
 
-        unsafe {
-            (self.lib_soda.add_audio)(
-                self.handle,
-                buffer.as_ptr() as *const c_char,
-                (buffer.len() * 2) as c_int,
-            );
-        }
+        self.session.add_audio(&buffer);
 
         self.samples_written += buffer.len();
 
@@ -235,10 +476,51 @@ impl Recognizer for GoogleRecognizer {
     }
 
     async fn stop(&mut self) -> SpeechResult {
-        unsafe {
-            (self.lib_soda.soda_stop)(self.handle);
+        self.state.check_stop()?;
+        self.state = RecognizerState::Stopping;
+
+        if self.batch_mode && self.samples_written > 0 {
+            self.wait_for_end_of_audio().await;
         }
 
+        self.session.stop();
+        self.state = RecognizerState::Stopped;
+
+        Ok(())
+    }
+
+    async fn abort(&mut self) -> SpeechResult {
+        self.state.check_abort()?;
+
+        // libsoda has no way to drop the buffered audio, so the session is stopped
+        // without marking the end of audio and its remaining responses are ignored
+        self.callback_context
+            .is_aborted
+            .store(true, Ordering::SeqCst);
+        self.session.stop();
+
+        let _ = self.callback_context.sender.send(RecognitionEvent::Stop);
+        self.state = RecognizerState::Stopped;
+
+        Ok(())
+    }
+
+    async fn pause(&mut self) -> SpeechResult {
+        self.state.check_pause()?;
+
+        // the session keeps running, it just doesn't get any audio
+        self.state = RecognizerState::Paused;
+        Ok(())
+    }
+
+    async fn resume(&mut self) -> SpeechResult {
+        self.state.check_resume()?;
+
+        // the time spent in pause doesn't count to the realtime delay
+        let audio_ms = (self.samples_written as u64 * 1000u64)
+            / (self.converter.output_format().sample_rate as u64);
+        self.start_time = Instant::now() - Duration::from_millis(audio_ms);
+        self.state = RecognizerState::Running;
         Ok(())
     }
 }
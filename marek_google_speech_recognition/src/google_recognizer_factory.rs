@@ -1,8 +1,8 @@
+use crate::language_packs::available_languages;
 use crate::GoogleRecognizer;
-use futures::channel::mpsc::UnboundedReceiver;
 use libsoda_sys::LibSoda;
 use marek_speech_recognition_api::{
-    RecognitionEvent, Recognizer, RecognizerFactory, RecognizerOptions, SpeechError, SpeechResult,
+    EventReceiver, Recognizer, RecognizerFactory, RecognizerOptions, SpeechError, SpeechResult,
 };
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -10,6 +10,7 @@ use std::sync::Arc;
 pub struct GoogleRecognizerFactory {
     lib_soda: Arc<LibSoda>,
     language_packs_folder: PathBuf,
+    batch_mode: bool,
 }
 
 impl GoogleRecognizerFactory {
@@ -22,24 +23,35 @@ impl GoogleRecognizerFactory {
         Ok(Self {
             lib_soda: Arc::new(lib_soda),
             language_packs_folder: language_packs_folder.into(),
+            batch_mode: false,
         })
     }
+
+    /// In batch mode `write` doesn't wait for the real time of the audio.
+    /// libsoda buffers the audio and `stop` waits until it is processed,
+    /// so whole recordings can be transcribed without pacing them by the caller.
+    pub fn with_batch_mode(mut self, batch_mode: bool) -> Self {
+        self.batch_mode = batch_mode;
+        self
+    }
 }
 
 impl RecognizerFactory for GoogleRecognizerFactory {
     fn create_recognizer(
         &mut self,
         recognizer_options: RecognizerOptions,
-    ) -> SpeechResult<(
-        Box<dyn Recognizer + Send>,
-        UnboundedReceiver<RecognitionEvent>,
-    )> {
+    ) -> SpeechResult<(Box<dyn Recognizer + Send>, EventReceiver)> {
         let (recognizer, receiver) = GoogleRecognizer::new(
             self.lib_soda.clone(),
             &self.language_packs_folder,
             recognizer_options,
+            self.batch_mode,
         )?;
 
         Ok((Box::new(recognizer), receiver))
     }
+
+    fn available_languages(&self) -> SpeechResult<Vec<String>> {
+        available_languages(&self.language_packs_folder)
+    }
 }
@@ -0,0 +1,59 @@
+use marek_speech_recognition_api::{SpeechError, SpeechResult};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Returns the `SODAModels` folder of the newest valid version of the language pack.
+///
+/// The language packs are installed by Chrome as `<language>/<version>/SODAModels`.
+pub(crate) fn find_language_pack(
+    language_packs_folder: &Path,
+    language: &str,
+) -> SpeechResult<PathBuf> {
+    let folder = language_packs_folder.join(language);
+    let versions =
+        fs::read_dir(&folder).map_err(|_| SpeechError::LanguageFolderError(folder.clone()))?;
+
+    versions
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let version = parse_version(entry.file_name().to_str()?)?;
+            let models_folder = entry.path().join("SODAModels");
+            is_valid_models_folder(&models_folder).then_some((version, models_folder))
+        })
+        .max_by(|(version1, _), (version2, _)| version1.cmp(version2))
+        .map(|(_, models_folder)| models_folder)
+        .ok_or(SpeechError::LanguageFolderError(folder))
+}
+
+/// Returns the sorted names of the languages with a valid language pack.
+pub(crate) fn available_languages(language_packs_folder: &Path) -> SpeechResult<Vec<String>> {
+    let languages = fs::read_dir(language_packs_folder)
+        .map_err(|_| SpeechError::LanguageFolderError(language_packs_folder.to_path_buf()))?;
+
+    let mut languages = languages
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|language| find_language_pack(language_packs_folder, language).is_ok())
+        .collect::<Vec<_>>();
+    languages.sort();
+
+    Ok(languages)
+}
+
+/// Parses version folder names like "1.3036.0", so "10.0" is newer than "9.0".
+fn parse_version(name: &str) -> Option<Vec<u64>> {
+    name.split('.').map(|part| part.parse().ok()).collect()
+}
+
+/// Partially downloaded or removed language packs leave incomplete folders behind,
+/// so the pack needs the recognizer configs (like `configs/ONDEVICE_MEDIUM_CONTINUOUS.config`).
+fn is_valid_models_folder(folder: &Path) -> bool {
+    fs::read_dir(folder.join("configs"))
+        .map(|entries| {
+            entries.filter_map(|entry| entry.ok()).any(|entry| {
+                entry.path().extension().is_some_and(|ext| ext == "config")
+                    && entry.file_type().is_ok_and(|file_type| file_type.is_file())
+            })
+        })
+        .unwrap_or(false)
+}
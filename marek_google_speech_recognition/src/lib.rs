@@ -1,5 +1,6 @@
 mod google_recognizer;
 mod google_recognizer_factory;
+mod language_packs;
 
 pub use google_recognizer::GoogleRecognizer;
 pub use google_recognizer_factory::GoogleRecognizerFactory;
@@ -0,0 +1,827 @@
+//! Runs `GoogleRecognizer` with `fake_libsoda`, which plays back scripted responses.
+
+use futures::StreamExt;
+use libsoda_sys::soda_endpoint_event::EndpointType;
+use libsoda_sys::soda_recognition_result::{FinalResultEndpointReason, ResultType};
+use libsoda_sys::soda_response::SodaMessageType;
+use libsoda_sys::{
+    ExtendedSodaConfigMsg, HypothesisPart, SodaAudioLevelInfo, SodaEndpointEvent, SodaLangIdEvent,
+    SodaRecognitionResult, SodaResponse, TimingMetrics,
+};
+use marek_google_speech_recognition::GoogleRecognizerFactory;
+use marek_speech_recognition_api::{
+    AudioLevelMeter, EndpointReason, EventChannelOptions, EventFilter, EventKind,
+    EventOverflowPolicy, RecognitionEvent, RecognitionMode, RecognizerFactory, RecognizerOptions,
+    RecognizerState, SpeechError, Word,
+};
+use prost::Message;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+const AT_MARK_DONE: u64 = u64::MAX;
+const AFTER_MARK_DONE: u64 = u64::MAX / 2;
+
+/// Builds `libsoda.so` from `fake_libsoda` and returns its folder.
+/// The library is built only once, the tests running in parallel share it.
+fn build_fake_libsoda() -> PathBuf {
+    static LIBRARY_FOLDER: OnceLock<PathBuf> = OnceLock::new();
+
+    LIBRARY_FOLDER
+        .get_or_init(|| {
+            // the test executable is in `target/<profile>/deps`
+            let exe = std::env::current_exe().unwrap();
+            let profile_dir = exe.parent().unwrap().parent().unwrap().to_path_buf();
+            let target_dir = profile_dir.parent().unwrap();
+
+            let mut command = Command::new(env!("CARGO"));
+            command
+                .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join(".."))
+                .args(["build", "-p", "fake_libsoda", "--target-dir"])
+                .arg(target_dir);
+            if profile_dir.file_name().unwrap() == "release" {
+                command.arg("--release");
+            }
+            let status = command.status().unwrap();
+            assert!(status.success(), "cannot build fake_libsoda");
+
+            profile_dir
+        })
+        .clone()
+}
+
+/// Creates the files that make the language pack valid.
+fn create_configs(models_folder: &Path) {
+    let configs_folder = models_folder.join("configs");
+    fs::create_dir_all(&configs_folder).unwrap();
+    fs::write(configs_folder.join("ONDEVICE_MEDIUM_CONTINUOUS.config"), "").unwrap();
+}
+
+/// Creates the language packs folder with the script for "en-US".
+fn create_language_packs(name: &str, script: &[(u64, SodaResponse)]) -> (PathBuf, PathBuf) {
+    let language_packs_folder =
+        std::env::temp_dir().join(format!("fake_libsoda_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&language_packs_folder);
+
+    let models_folder = language_packs_folder
+        .join("en-US")
+        .join("1.0")
+        .join("SODAModels");
+    create_configs(&models_folder);
+
+    let mut data = Vec::new();
+    for (at_audio_ms, response) in script {
+        let response = response.encode_to_vec();
+        data.extend_from_slice(&at_audio_ms.to_le_bytes());
+        data.extend_from_slice(&(response.len() as u32).to_le_bytes());
+        data.extend_from_slice(&response);
+    }
+    fs::write(models_folder.join("fake_soda_script.bin"), data).unwrap();
+
+    (language_packs_folder, models_folder)
+}
+
+fn endpoint(endpoint_type: EndpointType, time_usec: i64) -> SodaResponse {
+    SodaResponse {
+        soda_type: Some(SodaMessageType::Endpoint as i32),
+        endpoint_event: Some(SodaEndpointEvent {
+            endpoint_type: Some(endpoint_type as i32),
+            timing_metrics: Some(TimingMetrics {
+                event_end_time_usec: Some(time_usec),
+                ..TimingMetrics::default()
+            }),
+        }),
+        ..SodaResponse::default()
+    }
+}
+
+fn recognition(
+    hypothesis: &[&str],
+    result_type: ResultType,
+    endpoint_reason: FinalResultEndpointReason,
+    parts: &[(&str, i64)],
+    start_time_usec: i64,
+    end_time_usec: i64,
+) -> SodaResponse {
+    SodaResponse {
+        soda_type: Some(SodaMessageType::Recognition as i32),
+        recognition_result: Some(SodaRecognitionResult {
+            hypothesis: hypothesis.iter().map(|text| text.to_string()).collect(),
+            result_type: Some(result_type as i32),
+            endpoint_reason: Some(endpoint_reason as i32),
+            timing_metrics: Some(TimingMetrics {
+                audio_start_time_usec: Some(start_time_usec),
+                event_end_time_usec: Some(end_time_usec),
+                ..TimingMetrics::default()
+            }),
+            hypothesis_part: parts
+                .iter()
+                .map(|(text, alignment_ms)| HypothesisPart {
+                    text: vec![text.to_string()],
+                    alignment_ms: Some(*alignment_ms),
+                })
+                .collect(),
+        }),
+        ..SodaResponse::default()
+    }
+}
+
+#[tokio::test]
+async fn google_recognizer_reports_scripted_responses() {
+    let library_folder = build_fake_libsoda();
+    let (language_packs_folder, models_folder) = create_language_packs(
+        "events",
+        &[
+            (100, endpoint(EndpointType::StartOfSpeech, 100000)),
+            (
+                200,
+                SodaResponse {
+                    soda_type: Some(SodaMessageType::AudioLevel as i32),
+                    audio_level_info: Some(SodaAudioLevelInfo {
+                        rms: Some(0.25f32),
+                        audio_level: Some(0.5f32),
+                        audio_time_usec: Some(200000),
+                    }),
+                    ..SodaResponse::default()
+                },
+            ),
+            (
+                300,
+                recognition(
+                    &["hello"],
+                    ResultType::Partial,
+                    FinalResultEndpointReason::EndpointUnknown,
+                    &[],
+                    100000,
+                    300000,
+                ),
+            ),
+            (
+                500,
+                SodaResponse {
+                    soda_type: Some(SodaMessageType::Langid as i32),
+                    langid_event: Some(SodaLangIdEvent {
+                        language: Some("en-us".to_string()),
+                        confidence_level: Some(3),
+                    }),
+                    ..SodaResponse::default()
+                },
+            ),
+            (
+                AT_MARK_DONE,
+                recognition(
+                    &["Hello world.", "hello word"],
+                    ResultType::Final,
+                    FinalResultEndpointReason::EndpointEndOfAudio,
+                    &[("Hello", 0), ("world.", 400)],
+                    100000,
+                    900000,
+                ),
+            ),
+        ],
+    );
+
+    let mut factory = GoogleRecognizerFactory::new(&library_folder, &language_packs_folder)
+        .unwrap()
+        .with_batch_mode(true);
+    let mut options = RecognizerOptions::default();
+    options.max_alternatives = 2;
+    let (mut recognizer, receiver) = factory.create_recognizer(options).unwrap();
+
+    recognizer.start().await.unwrap();
+    let audio = vec![0i16; 16000];
+    for chunk in audio.chunks(1600) {
+        recognizer.write(chunk.into()).await.unwrap();
+    }
+    recognizer.stop().await.unwrap();
+
+    // the event stream ends when the recognizer is dropped
+    drop(recognizer);
+    let events = receiver.collect::<Vec<_>>().await;
+
+    let hello_world_words = vec![
+        Word {
+            conf: 1.0f32,
+            start_time_usec: 100000,
+            end_time_usec: 500000,
+            word: "Hello".to_string(),
+        },
+        Word {
+            conf: 1.0f32,
+            start_time_usec: 500000,
+            end_time_usec: 900000,
+            word: "world.".to_string(),
+        },
+    ];
+
+    assert_eq!(events.len(), 7);
+    assert_eq!(events[0], RecognitionEvent::Start);
+    assert_eq!(
+        events[1],
+        RecognitionEvent::StartOfSpeech {
+            audio_time_usec: Some(100000)
+        }
+    );
+    assert_eq!(
+        events[2],
+        RecognitionEvent::AudioLevel {
+            rms: 0.25f32,
+            level: AudioLevelMeter::level_of(0.25f32),
+            speech_likelihood: Some(0.5f32),
+            audio_time_usec: Some(200000)
+        }
+    );
+    assert!(matches!(
+        &events[3],
+        RecognitionEvent::Recognition { text, is_final: false, endpoint_reason: None, .. }
+            if text == "hello"
+    ));
+    assert_eq!(
+        events[4],
+        RecognitionEvent::Language {
+            id: "en-us".to_string(),
+            confidence_level: Some(3)
+        }
+    );
+    match &events[5] {
+        RecognitionEvent::Recognition {
+            text,
+            is_final,
+            audio_start_time_usec,
+            audio_end_time_usec,
+            words,
+            endpoint_reason,
+            alternatives,
+            speaker_vector,
+        } => {
+            assert_eq!(text, "Hello world.");
+            assert!(is_final);
+            assert_eq!(*audio_start_time_usec, Some(100000));
+            assert_eq!(*audio_end_time_usec, Some(900000));
+            assert_eq!(words.as_ref(), Some(&hello_world_words));
+            assert_eq!(*endpoint_reason, Some(EndpointReason::EndOfAudio));
+            assert_eq!(alternatives.len(), 2);
+            assert_eq!(alternatives[1].text, "hello word");
+            assert_eq!(*speaker_vector, None);
+        }
+        event => panic!("unexpected event: {:?}", event),
+    }
+    assert_eq!(events[6], RecognitionEvent::Stop);
+
+    // the config passed to libsoda
+    let config = ExtendedSodaConfigMsg::decode(
+        fs::read(models_folder.join("fake_soda_config.bin"))
+            .unwrap()
+            .as_slice(),
+    )
+    .unwrap();
+    assert_eq!(config.sample_rate, Some(16000));
+    assert_eq!(config.channel_count, Some(1));
+    assert_eq!(config.simulate_realtime_testonly, Some(false));
+    assert_eq!(
+        config.language_pack_directory(),
+        models_folder.to_str().unwrap()
+    );
+
+    let _ = fs::remove_dir_all(&language_packs_folder);
+}
+
+#[tokio::test]
+async fn google_recognizer_batch_stop_waits_while_libsoda_decodes() {
+    let library_folder = build_fake_libsoda();
+
+    // libsoda responds every 1.5 seconds and the final result comes after 6 seconds
+    let audio_level = |audio_time_usec: i64| SodaResponse {
+        soda_type: Some(SodaMessageType::AudioLevel as i32),
+        audio_level_info: Some(SodaAudioLevelInfo {
+            rms: Some(0.25f32),
+            audio_level: Some(0.5f32),
+            audio_time_usec: Some(audio_time_usec),
+        }),
+        ..SodaResponse::default()
+    };
+    let final_result = recognition(
+        &["hello world"],
+        ResultType::Final,
+        FinalResultEndpointReason::EndpointEndOfAudio,
+        &[],
+        0,
+        1000000,
+    );
+    let (language_packs_folder, _) = create_language_packs(
+        "batch_decoding",
+        &[
+            (AFTER_MARK_DONE + 1500, audio_level(250000)),
+            (AFTER_MARK_DONE + 3000, audio_level(500000)),
+            (AFTER_MARK_DONE + 4500, audio_level(750000)),
+            (AFTER_MARK_DONE + 6000, final_result),
+        ],
+    );
+
+    let mut factory = GoogleRecognizerFactory::new(&library_folder, &language_packs_folder)
+        .unwrap()
+        .with_batch_mode(true);
+    let (mut recognizer, receiver) = factory
+        .create_recognizer(RecognizerOptions::default())
+        .unwrap();
+
+    recognizer.start().await.unwrap();
+    recognizer.write((&vec![0i16; 16000]).into()).await.unwrap();
+    recognizer.stop().await.unwrap();
+
+    drop(recognizer);
+    let events = receiver.collect::<Vec<_>>().await;
+
+    let final_index = events
+        .iter()
+        .position(|event| {
+            matches!(
+                event,
+                RecognitionEvent::Recognition {
+                    endpoint_reason: Some(EndpointReason::EndOfAudio),
+                    ..
+                }
+            )
+        })
+        .expect("the final result is not reported");
+    assert_eq!(
+        events[final_index..].last(),
+        Some(&RecognitionEvent::Stop),
+        "the final result comes before the end of the session"
+    );
+
+    let _ = fs::remove_dir_all(&language_packs_folder);
+}
+
+#[tokio::test]
+async fn google_recognizer_converts_audio_format() {
+    let library_folder = build_fake_libsoda();
+    let (language_packs_folder, models_folder) = create_language_packs(
+        "audio_format",
+        &[(500, endpoint(EndpointType::EndOfSpeech, 500000))],
+    );
+
+    let mut factory =
+        GoogleRecognizerFactory::new(&library_folder, &language_packs_folder).unwrap();
+    let mut options = RecognizerOptions::default();
+    options.audio_format = marek_speech_recognition_api::AudioFormat::new(
+        marek_speech_recognition_api::SampleFormat::F32,
+        2,
+        48000,
+    );
+    let (mut recognizer, receiver) = factory.create_recognizer(options).unwrap();
+
+    recognizer.start().await.unwrap();
+    // one second of stereo audio
+    let audio = vec![0f32; 2 * 48000];
+    recognizer.write((&audio).into()).await.unwrap();
+    recognizer.stop().await.unwrap();
+
+    drop(recognizer);
+    let events = receiver.collect::<Vec<_>>().await;
+
+    assert_eq!(
+        events,
+        vec![
+            RecognitionEvent::Start,
+            RecognitionEvent::EndOfSpeech {
+                audio_time_usec: Some(500000)
+            },
+            RecognitionEvent::Stop,
+        ]
+    );
+
+    // libsoda gets mono audio in the original sample rate
+    let config = ExtendedSodaConfigMsg::decode(
+        fs::read(models_folder.join("fake_soda_config.bin"))
+            .unwrap()
+            .as_slice(),
+    )
+    .unwrap();
+    assert_eq!(config.sample_rate, Some(48000));
+    assert_eq!(config.channel_count, Some(1));
+
+    let _ = fs::remove_dir_all(&language_packs_folder);
+}
+
+#[test]
+fn google_recognizer_factory_finds_language_packs() {
+    let library_folder = build_fake_libsoda();
+    let language_packs_folder =
+        std::env::temp_dir().join(format!("fake_libsoda_packs_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&language_packs_folder);
+
+    let create_pack = |language: &str, version: &str, is_valid: bool| {
+        let models_folder = language_packs_folder
+            .join(language)
+            .join(version)
+            .join("SODAModels");
+        fs::create_dir_all(&models_folder).unwrap();
+        fs::write(models_folder.join("metadata"), "").unwrap();
+        if is_valid {
+            create_configs(&models_folder);
+        }
+        models_folder
+    };
+    create_pack("en-US", "9.0", true);
+    let newest_valid_folder = create_pack("en-US", "10.0", true);
+    create_pack("en-US", "11.0", false);
+    create_pack("de-DE", "1.0", false);
+    fs::create_dir_all(
+        language_packs_folder
+            .join("it-IT")
+            .join("1.0")
+            .join("SODAModels")
+            .join("configs"),
+    )
+    .unwrap();
+    create_pack("pl-PL", "1.2.3", true);
+    fs::create_dir_all(language_packs_folder.join("fr-FR").join("latest")).unwrap();
+
+    let mut factory =
+        GoogleRecognizerFactory::new(&library_folder, &language_packs_folder).unwrap();
+    assert_eq!(
+        factory.available_languages().unwrap(),
+        vec!["en-US".to_string(), "pl-PL".to_string()]
+    );
+
+    let (recognizer, _receiver) = factory
+        .create_recognizer(RecognizerOptions::default())
+        .unwrap();
+    drop(recognizer);
+    assert!(newest_valid_folder.join("fake_soda_config.bin").exists());
+
+    let mut options = RecognizerOptions::default();
+    options.language = "de-DE".to_string();
+    assert!(factory.create_recognizer(options).is_err());
+
+    let _ = fs::remove_dir_all(&language_packs_folder);
+}
+
+#[tokio::test]
+async fn google_recognizer_matches_commands() {
+    let library_folder = build_fake_libsoda();
+    let (language_packs_folder, _) = create_language_packs(
+        "commands",
+        &[
+            (
+                100,
+                recognition(
+                    // libsoda formats the results by default
+                    &["Turn on the light."],
+                    ResultType::Final,
+                    FinalResultEndpointReason::EndpointEndOfSpeech,
+                    &[],
+                    0,
+                    100000,
+                ),
+            ),
+            (
+                200,
+                recognition(
+                    &["Open the window."],
+                    ResultType::Final,
+                    FinalResultEndpointReason::EndpointEndOfSpeech,
+                    &[],
+                    100000,
+                    200000,
+                ),
+            ),
+        ],
+    );
+
+    let mut factory =
+        GoogleRecognizerFactory::new(&library_folder, &language_packs_folder).unwrap();
+    let mut options = RecognizerOptions::default();
+    options.mode = RecognitionMode::Commands(vec![
+        "turn off the light".to_string(),
+        "turn on the light".to_string(),
+    ]);
+    let (mut recognizer, receiver) = factory.create_recognizer(options).unwrap();
+
+    recognizer.start().await.unwrap();
+    recognizer.write((&vec![0i16; 3200]).into()).await.unwrap();
+    recognizer.stop().await.unwrap();
+
+    drop(recognizer);
+    let events = receiver
+        .filter(|event| {
+            futures::future::ready(matches!(
+                event,
+                RecognitionEvent::Command { .. } | RecognitionEvent::NoMatch { .. }
+            ))
+        })
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(
+        events,
+        vec![
+            RecognitionEvent::Command {
+                index: 1,
+                phrase: "turn on the light".to_string(),
+                confidence: 1.0f32,
+            },
+            RecognitionEvent::NoMatch {
+                text: "Open the window.".to_string(),
+                confidence: 1.0f32,
+            },
+        ]
+    );
+
+    let _ = fs::remove_dir_all(&language_packs_folder);
+}
+
+#[tokio::test]
+async fn google_recognizer_can_be_restarted() {
+    let library_folder = build_fake_libsoda();
+    let (language_packs_folder, _) = create_language_packs(
+        "restart",
+        &[(100, endpoint(EndpointType::StartOfSpeech, 100000))],
+    );
+
+    let mut factory =
+        GoogleRecognizerFactory::new(&library_folder, &language_packs_folder).unwrap();
+    let (mut recognizer, receiver) = factory
+        .create_recognizer(RecognizerOptions::default())
+        .unwrap();
+    let audio = vec![0i16; 3200];
+
+    assert_eq!(recognizer.state(), RecognizerState::Created);
+    assert_eq!(
+        recognizer.write((&audio).into()).await,
+        Err(SpeechError::InvalidState(RecognizerState::Created))
+    );
+    assert_eq!(
+        recognizer.stop().await,
+        Err(SpeechError::InvalidState(RecognizerState::Created))
+    );
+
+    for _ in 0..2 {
+        recognizer.start().await.unwrap();
+        assert_eq!(recognizer.state(), RecognizerState::Running);
+        assert_eq!(
+            recognizer.start().await,
+            Err(SpeechError::InvalidState(RecognizerState::Running))
+        );
+
+        recognizer.write((&audio).into()).await.unwrap();
+        recognizer.stop().await.unwrap();
+        assert_eq!(recognizer.state(), RecognizerState::Stopped);
+    }
+
+    drop(recognizer);
+    let events = receiver.collect::<Vec<_>>().await;
+
+    let session_events = vec![
+        RecognitionEvent::Start,
+        RecognitionEvent::StartOfSpeech {
+            audio_time_usec: Some(100000),
+        },
+        RecognitionEvent::Stop,
+    ];
+    assert_eq!(events, [session_events.clone(), session_events].concat());
+
+    let _ = fs::remove_dir_all(&language_packs_folder);
+}
+
+#[tokio::test]
+async fn google_recognizer_can_be_paused_and_aborted() {
+    let library_folder = build_fake_libsoda();
+    let (language_packs_folder, _) = create_language_packs(
+        "abort",
+        &[
+            (100, endpoint(EndpointType::StartOfSpeech, 100000)),
+            (200, endpoint(EndpointType::EndOfSpeech, 200000)),
+            (
+                AT_MARK_DONE,
+                recognition(
+                    &["hello"],
+                    ResultType::Final,
+                    FinalResultEndpointReason::EndpointEndOfAudio,
+                    &[("hello", 0)],
+                    100000,
+                    200000,
+                ),
+            ),
+        ],
+    );
+
+    let mut factory =
+        GoogleRecognizerFactory::new(&library_folder, &language_packs_folder).unwrap();
+    let (mut recognizer, receiver) = factory
+        .create_recognizer(RecognizerOptions::default())
+        .unwrap();
+    let audio = vec![0i16; 1600];
+
+    recognizer.start().await.unwrap();
+    recognizer.write((&audio).into()).await.unwrap();
+
+    recognizer.pause().await.unwrap();
+    assert_eq!(recognizer.state(), RecognizerState::Paused);
+    assert_eq!(
+        recognizer.pause().await,
+        Err(SpeechError::InvalidState(RecognizerState::Paused))
+    );
+
+    // ignored while paused
+    recognizer.write((&audio).into()).await.unwrap();
+
+    recognizer.resume().await.unwrap();
+    assert_eq!(recognizer.state(), RecognizerState::Running);
+    assert_eq!(
+        recognizer.resume().await,
+        Err(SpeechError::InvalidState(RecognizerState::Running))
+    );
+
+    recognizer.abort().await.unwrap();
+    assert_eq!(recognizer.state(), RecognizerState::Stopped);
+    assert_eq!(
+        recognizer.abort().await,
+        Err(SpeechError::InvalidState(RecognizerState::Stopped))
+    );
+
+    // the next session is not affected by the abort
+    recognizer.start().await.unwrap();
+    recognizer.write((&audio).into()).await.unwrap();
+    recognizer.stop().await.unwrap();
+
+    drop(recognizer);
+    let events = receiver.collect::<Vec<_>>().await;
+
+    assert_eq!(
+        events,
+        vec![
+            RecognitionEvent::Start,
+            RecognitionEvent::StartOfSpeech {
+                audio_time_usec: Some(100000),
+            },
+            RecognitionEvent::Stop,
+            RecognitionEvent::Start,
+            RecognitionEvent::StartOfSpeech {
+                audio_time_usec: Some(100000),
+            },
+            RecognitionEvent::Stop,
+        ]
+    );
+
+    let _ = fs::remove_dir_all(&language_packs_folder);
+}
+
+#[tokio::test]
+async fn google_recognizer_drops_partial_results_when_channel_is_full() {
+    let library_folder = build_fake_libsoda();
+    let partial = |text: &str, time_usec: i64| {
+        recognition(
+            &[text],
+            ResultType::Partial,
+            FinalResultEndpointReason::EndpointUnknown,
+            &[],
+            0,
+            time_usec,
+        )
+    };
+    let (language_packs_folder, _) = create_language_packs(
+        "overflow",
+        &[
+            (100, partial("one", 100000)),
+            (200, partial("one two", 200000)),
+            (300, partial("one two three", 300000)),
+            (
+                400,
+                recognition(
+                    &["one two three"],
+                    ResultType::Final,
+                    FinalResultEndpointReason::EndpointEndOfSpeech,
+                    &[],
+                    0,
+                    400000,
+                ),
+            ),
+            (500, partial("four", 500000)),
+        ],
+    );
+
+    let mut factory =
+        GoogleRecognizerFactory::new(&library_folder, &language_packs_folder).unwrap();
+    let mut options = RecognizerOptions::default();
+    options.event_channel = EventChannelOptions {
+        capacity: Some(2),
+        overflow_policy: EventOverflowPolicy::CoalescePartials,
+    };
+    let (mut recognizer, mut receiver) = factory.create_recognizer(options).unwrap();
+
+    // the events are not received until the end
+    recognizer.start().await.unwrap();
+    recognizer.write((&vec![0i16; 8000]).into()).await.unwrap();
+    recognizer.stop().await.unwrap();
+
+    drop(recognizer);
+    let events = receiver.by_ref().collect::<Vec<_>>().await;
+
+    assert_eq!(
+        events,
+        vec![
+            RecognitionEvent::Start,
+            RecognitionEvent::Recognition {
+                text: "one two three".to_string(),
+                is_final: true,
+                audio_start_time_usec: Some(0),
+                audio_end_time_usec: Some(400000),
+                words: None,
+                endpoint_reason: Some(EndpointReason::EndOfSpeech),
+                alternatives: Vec::new(),
+                speaker_vector: None,
+            },
+            RecognitionEvent::Stop,
+        ]
+    );
+    assert_eq!(receiver.dropped_events(), 4);
+
+    let _ = fs::remove_dir_all(&language_packs_folder);
+}
+
+#[tokio::test]
+async fn google_recognizer_sends_events_to_subscribers() {
+    let library_folder = build_fake_libsoda();
+    let partial = recognition(
+        &["hello"],
+        ResultType::Partial,
+        FinalResultEndpointReason::EndpointUnknown,
+        &[],
+        0,
+        100000,
+    );
+    let final_result = recognition(
+        &["hello world"],
+        ResultType::Final,
+        FinalResultEndpointReason::EndpointEndOfSpeech,
+        &[],
+        0,
+        300000,
+    );
+    let (language_packs_folder, _) =
+        create_language_packs("subscribers", &[(100, partial), (300, final_result)]);
+
+    let mut factory =
+        GoogleRecognizerFactory::new(&library_folder, &language_packs_folder).unwrap();
+    let (mut recognizer, receiver) = factory
+        .create_recognizer(RecognizerOptions::default())
+        .unwrap();
+    let final_receiver = recognizer.subscribe(
+        EventFilter::only(&[EventKind::FinalRecognition]),
+        EventChannelOptions::default(),
+    );
+
+    recognizer.start().await.unwrap();
+    recognizer.write((&vec![0i16; 1600]).into()).await.unwrap();
+
+    // subscribed in the middle of the utterance
+    let late_receiver = recognizer.subscribe(EventFilter::all(), EventChannelOptions::default());
+
+    recognizer.write((&vec![0i16; 3200]).into()).await.unwrap();
+    recognizer.stop().await.unwrap();
+
+    drop(recognizer);
+    let events = receiver.collect::<Vec<_>>().await;
+    let final_events = final_receiver.collect::<Vec<_>>().await;
+    let late_events = late_receiver.collect::<Vec<_>>().await;
+
+    let partial_event = RecognitionEvent::Recognition {
+        text: "hello".to_string(),
+        is_final: false,
+        audio_start_time_usec: Some(0),
+        audio_end_time_usec: Some(100000),
+        words: None,
+        endpoint_reason: None,
+        alternatives: Vec::new(),
+        speaker_vector: None,
+    };
+    let final_event = RecognitionEvent::Recognition {
+        text: "hello world".to_string(),
+        is_final: true,
+        audio_start_time_usec: Some(0),
+        audio_end_time_usec: Some(300000),
+        words: None,
+        endpoint_reason: Some(EndpointReason::EndOfSpeech),
+        alternatives: Vec::new(),
+        speaker_vector: None,
+    };
+    assert_eq!(
+        events,
+        vec![
+            RecognitionEvent::Start,
+            partial_event.clone(),
+            final_event.clone(),
+            RecognitionEvent::Stop,
+        ]
+    );
+    assert_eq!(final_events, vec![final_event.clone()]);
+    assert_eq!(
+        late_events,
+        vec![partial_event, final_event, RecognitionEvent::Stop]
+    );
+
+    let _ = fs::remove_dir_all(&language_packs_folder);
+}
@@ -0,0 +1,7 @@
+mod mock_recognizer;
+mod mock_recognizer_factory;
+mod mock_script;
+
+pub use mock_recognizer::MockRecognizer;
+pub use mock_recognizer_factory::MockRecognizerFactory;
+pub use mock_script::{MockAction, MockScript, MockStep};
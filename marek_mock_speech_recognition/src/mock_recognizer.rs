@@ -0,0 +1,499 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use marek_speech_recognition_api::{
+    event_channel, AudioBuffer, AudioConverter, AudioFormat, AudioLevelMeter, EndpointReason,
+    EventChannelOptions, EventFilter, EventReceiver, EventSender, RecognitionEvent, Recognizer,
+    RecognizerInfo, RecognizerState, SpeechError, SpeechResult, Word,
+};
+use tokio::time::{sleep, Instant};
+
+use crate::{MockAction, MockScript};
+
+pub struct MockRecognizer {
+    info: RecognizerInfo,
+    converter: AudioConverter,
+    script: MockScript,
+    sender: EventSender,
+    state: RecognizerState,
+    next_step: usize,
+    start_time: Instant,
+    samples_written: usize,
+    commands: Vec<String>,
+    audio_level_meter: Option<AudioLevelMeter>,
+}
+
+impl MockRecognizer {
+    pub(crate) fn new(
+        info: RecognizerInfo,
+        audio_format: AudioFormat,
+        event_channel_options: EventChannelOptions,
+        script: MockScript,
+        reports_audio_level: bool,
+    ) -> SpeechResult<(Self, EventReceiver)> {
+        let (sender, receiver) = event_channel(event_channel_options);
+
+        // steps are keyed to mono samples in the original sample rate
+        let converter = AudioConverter::new(
+            audio_format,
+            AudioFormat::mono_i16(audio_format.sample_rate),
+        )?;
+
+        Ok((
+            Self {
+                info,
+                converter,
+                script,
+                sender,
+                state: RecognizerState::Created,
+                next_step: 0,
+                start_time: Instant::now(),
+                samples_written: 0,
+                commands: Vec::new(),
+                audio_level_meter: reports_audio_level
+                    .then(|| AudioLevelMeter::new(audio_format.sample_rate)),
+            },
+            receiver,
+        ))
+    }
+
+    /// Total number of mono samples written since the last `start`.
+    pub fn samples_written(&self) -> usize {
+        self.samples_written
+    }
+
+    /// The commands set by the last `set_commands` call.
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+
+    fn execute(&self, action: &MockAction, at_sample: usize) -> SpeechResult {
+        let event = match action {
+            MockAction::Event(event) => event.clone(),
+            MockAction::StartOfSpeech => RecognitionEvent::StartOfSpeech {
+                audio_time_usec: Some(self.to_usec(at_sample)),
+            },
+            MockAction::EndOfSpeech => RecognitionEvent::EndOfSpeech {
+                audio_time_usec: Some(self.to_usec(at_sample)),
+            },
+            MockAction::EndOfUtterance => RecognitionEvent::EndOfUtterance {
+                audio_time_usec: Some(self.to_usec(at_sample)),
+            },
+            MockAction::EndOfAudio => RecognitionEvent::EndOfAudio {
+                audio_time_usec: Some(self.to_usec(at_sample)),
+            },
+            MockAction::Recognition {
+                text,
+                is_final,
+                endpoint_reason,
+                start_sample,
+            } => self.to_recognition_event(
+                text,
+                *is_final,
+                *endpoint_reason,
+                *start_sample,
+                at_sample,
+            ),
+            MockAction::Language(id) => RecognitionEvent::Language {
+                id: id.clone(),
+                confidence_level: None,
+            },
+            MockAction::Error(error) => {
+                let _ = self.sender.send(RecognitionEvent::Error(error.clone()));
+                return Err(error.clone());
+            }
+        };
+
+        // the receiver may be already dropped by the tested code
+        let _ = self.sender.send(event);
+
+        Ok(())
+    }
+
+    /// Sends the audio levels of the finished intervals, if enabled.
+    fn measure(&mut self, samples: &[i16]) {
+        if let Some(audio_level_meter) = &mut self.audio_level_meter {
+            for event in audio_level_meter.process(samples.into()) {
+                let _ = self.sender.send(event);
+            }
+        }
+    }
+
+    fn to_recognition_event(
+        &self,
+        text: &str,
+        is_final: bool,
+        endpoint_reason: Option<EndpointReason>,
+        start_sample: usize,
+        end_sample: usize,
+    ) -> RecognitionEvent {
+        let start_time_usec = self.to_usec(start_sample);
+        let end_time_usec = self.to_usec(end_sample.max(start_sample));
+
+        let words = text.split_whitespace().collect::<Vec<_>>();
+        let word_duration_usec = (end_time_usec - start_time_usec) / words.len().max(1) as u64;
+
+        RecognitionEvent::Recognition {
+            text: text.to_string(),
+            is_final,
+            audio_start_time_usec: Some(start_time_usec),
+            audio_end_time_usec: Some(end_time_usec),
+            words: Some(
+                words
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, word)| Word {
+                        conf: 1.0f32,
+                        start_time_usec: start_time_usec + i as u64 * word_duration_usec,
+                        end_time_usec: start_time_usec + (i as u64 + 1) * word_duration_usec,
+                        word: word.to_string(),
+                    })
+                    .collect(),
+            ),
+            endpoint_reason,
+            alternatives: Vec::new(),
+            speaker_vector: None,
+        }
+    }
+
+    fn to_usec(&self, samples: usize) -> u64 {
+        samples as u64 * 1000000u64 / self.converter.output_format().sample_rate as u64
+    }
+}
+
+#[async_trait]
+impl Recognizer for MockRecognizer {
+    fn info(&self) -> &RecognizerInfo {
+        &self.info
+    }
+
+    fn state(&self) -> RecognizerState {
+        self.state
+    }
+
+    fn subscribe(&self, filter: EventFilter, options: EventChannelOptions) -> EventReceiver {
+        self.sender.subscribe(filter, options)
+    }
+
+    async fn start(&mut self) -> SpeechResult {
+        self.state.check_start()?;
+
+        self.next_step = 0;
+        self.samples_written = 0;
+        self.converter.reset();
+        if let Some(audio_level_meter) = &mut self.audio_level_meter {
+            audio_level_meter.reset();
+        }
+
+        let _ = self.sender.send(RecognitionEvent::Start);
+        self.state = RecognizerState::Running;
+
+        Ok(())
+    }
+
+    async fn write(&mut self, buffer: AudioBuffer<'_>) -> SpeechResult {
+        self.state.check_write()?;
+        if self.state == RecognizerState::Paused {
+            return Ok(());
+        }
+
+        let samples = self.converter.convert_i16(buffer)?;
+
+        // simulate the delay between buffers like realtime only backends do
+        if self.info.is_realtime_only {
+            if self.samples_written == 0 {
+                self.start_time = Instant::now();
+            } else {
+                let elapsed_ms = self.start_time.elapsed().as_millis() as u64;
+                let dest_time_ms = self.to_usec(self.samples_written) / 1000u64;
+                if dest_time_ms > elapsed_ms {
+                    sleep(Duration::from_millis(dest_time_ms - elapsed_ms)).await;
+                }
+            }
+        }
+
+        let buffer_start_sample = self.samples_written;
+        self.samples_written += samples.len();
+
+        // the audio is measured up to each step, so the events are ordered by time
+        let mut measured = 0;
+        while let Some(step) = self.script.steps().get(self.next_step) {
+            if step.at_sample > self.samples_written {
+                break;
+            }
+            self.next_step += 1;
+            let at_sample = step.at_sample;
+            let action = step.action.clone();
+
+            let step_position = at_sample.saturating_sub(buffer_start_sample);
+            if step_position > measured {
+                self.measure(&samples[measured..step_position]);
+                measured = step_position;
+            }
+
+            self.execute(&action, at_sample)?;
+        }
+        self.measure(&samples[measured..]);
+
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> SpeechResult {
+        self.state.check_stop()?;
+
+        let mut result = Ok(());
+        for action in self.script.stop_actions() {
+            if let Err(err) = self.execute(action, self.samples_written) {
+                result = Err(err);
+                break;
+            }
+        }
+
+        let _ = self.sender.send(RecognitionEvent::Stop);
+        self.state = RecognizerState::Stopped;
+
+        result
+    }
+
+    async fn abort(&mut self) -> SpeechResult {
+        self.state.check_abort()?;
+
+        // the remaining steps and the stop actions are skipped
+        let _ = self.sender.send(RecognitionEvent::Stop);
+        self.state = RecognizerState::Stopped;
+
+        Ok(())
+    }
+
+    async fn pause(&mut self) -> SpeechResult {
+        self.state.check_pause()?;
+        self.state = RecognizerState::Paused;
+        Ok(())
+    }
+
+    async fn resume(&mut self) -> SpeechResult {
+        self.state.check_resume()?;
+
+        // the time spent in pause doesn't count to the realtime delay
+        self.start_time =
+            Instant::now() - Duration::from_micros(self.to_usec(self.samples_written));
+        self.state = RecognizerState::Running;
+        Ok(())
+    }
+
+    async fn set_commands(&mut self, commands: Vec<String>) -> SpeechResult {
+        if !self.info.supports_command_updates {
+            return Err(SpeechError::RecognitionModeNotSupported(
+                "Updating commands".to_string(),
+            ));
+        }
+
+        self.commands = commands;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use marek_speech_recognition_api::{RecognizerFactory, RecognizerOptions};
+
+    use super::*;
+    use crate::MockRecognizerFactory;
+
+    fn create_recognizer(
+        factory: MockRecognizerFactory,
+    ) -> (Box<dyn Recognizer + Send>, EventReceiver) {
+        let mut factory = factory;
+        factory
+            .create_recognizer(RecognizerOptions::default())
+            .unwrap()
+    }
+
+    fn script() -> MockScript {
+        MockScript::new()
+            .start_of_speech(1600)
+            .final_result(4800, "hello world")
+            .on_stop(MockAction::EndOfAudio)
+    }
+
+    #[test]
+    fn events_do_not_depend_on_buffer_split() {
+        let run = |buffer_len: usize| {
+            let (mut recognizer, receiver) =
+                create_recognizer(MockRecognizerFactory::new().with_script("en-US", script()));
+            block_on(async {
+                recognizer.start().await.unwrap();
+                for _ in 0..(6400 / buffer_len) {
+                    recognizer
+                        .write((&vec![0i16; buffer_len]).into())
+                        .await
+                        .unwrap();
+                }
+                recognizer.stop().await.unwrap();
+                drop(recognizer);
+                receiver.collect::<Vec<_>>().await
+            })
+        };
+
+        let events = run(6400);
+        assert_eq!(events, run(100));
+
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[0], RecognitionEvent::Start);
+        assert_eq!(
+            events[1],
+            RecognitionEvent::StartOfSpeech {
+                audio_time_usec: Some(100000)
+            }
+        );
+        match &events[2] {
+            RecognitionEvent::Recognition {
+                text,
+                is_final: true,
+                audio_start_time_usec: Some(100000),
+                audio_end_time_usec: Some(300000),
+                words: Some(words),
+                ..
+            } => {
+                assert_eq!(text, "hello world");
+                assert_eq!(words[1].start_time_usec, 200000);
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert_eq!(
+            events[3],
+            RecognitionEvent::EndOfAudio {
+                audio_time_usec: Some(400000)
+            }
+        );
+        assert_eq!(events[4], RecognitionEvent::Stop);
+    }
+
+    #[test]
+    fn audio_level_is_ordered_with_script() {
+        let run = |buffer_len: usize| {
+            let (mut recognizer, receiver) = create_recognizer(
+                MockRecognizerFactory::new()
+                    .with_script("en-US", script())
+                    .with_audio_level(true),
+            );
+            block_on(async {
+                recognizer.start().await.unwrap();
+                for _ in 0..(6400 / buffer_len) {
+                    recognizer
+                        .write((&vec![1000i16; buffer_len]).into())
+                        .await
+                        .unwrap();
+                }
+                recognizer.stop().await.unwrap();
+                drop(recognizer);
+                receiver.collect::<Vec<_>>().await
+            })
+        };
+
+        let events = run(6400);
+        assert_eq!(events, run(100));
+
+        let times = events
+            .iter()
+            .map(|event| match event {
+                RecognitionEvent::AudioLevel {
+                    audio_time_usec, ..
+                } => ("level", *audio_time_usec),
+                RecognitionEvent::StartOfSpeech { audio_time_usec } => ("start", *audio_time_usec),
+                RecognitionEvent::Recognition {
+                    audio_end_time_usec,
+                    ..
+                } => ("final", *audio_end_time_usec),
+                RecognitionEvent::EndOfAudio { audio_time_usec } => ("end", *audio_time_usec),
+                _ => ("other", None),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            vec![
+                ("other", None),
+                ("level", Some(100000)),
+                ("start", Some(100000)),
+                ("level", Some(200000)),
+                ("level", Some(300000)),
+                ("final", Some(300000)),
+                ("level", Some(400000)),
+                ("end", Some(400000)),
+                ("other", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn scripted_error_is_returned_by_write() {
+        let script = MockScript::new().error(100, SpeechError::DecodingFailed("test".to_string()));
+        let (mut recognizer, _receiver) =
+            create_recognizer(MockRecognizerFactory::new().with_script("en-US", script));
+
+        block_on(async {
+            recognizer.start().await.unwrap();
+            assert_eq!(
+                recognizer.write((&vec![0i16; 100]).into()).await,
+                Err(SpeechError::DecodingFailed("test".to_string()))
+            );
+        });
+    }
+
+    #[test]
+    fn set_commands_follows_command_updates_flag() {
+        let (mut recognizer, _receiver) =
+            create_recognizer(MockRecognizerFactory::new().with_script("en-US", script()));
+        block_on(async {
+            recognizer
+                .set_commands(vec!["open".to_string()])
+                .await
+                .unwrap();
+        });
+        assert!(recognizer.info().supports_command_updates);
+
+        let (mut recognizer, _receiver) = create_recognizer(
+            MockRecognizerFactory::new()
+                .with_script("en-US", script())
+                .with_command_updates(false),
+        );
+        assert!(!recognizer.info().supports_command_updates);
+        block_on(async {
+            assert!(matches!(
+                recognizer.set_commands(vec!["open".to_string()]).await,
+                Err(SpeechError::RecognitionModeNotSupported(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn commands_are_stored() {
+        let (mut recognizer, _receiver) = MockRecognizer::new(
+            RecognizerInfo {
+                name: "Mock".to_string(),
+                is_realtime_only: false,
+                has_punctuation: true,
+                supports_command_updates: true,
+            },
+            AudioFormat::default(),
+            EventChannelOptions::default(),
+            script(),
+            false,
+        )
+        .unwrap();
+
+        block_on(recognizer.set_commands(vec!["open".to_string(), "close".to_string()])).unwrap();
+        assert_eq!(recognizer.commands(), &["open", "close"]);
+    }
+
+    #[test]
+    fn missing_script_is_reported() {
+        let mut factory = MockRecognizerFactory::new().with_script("pl", script());
+        assert!(matches!(
+            factory.create_recognizer(RecognizerOptions::default()),
+            Err(SpeechError::NoLanguageFound(_))
+        ));
+    }
+}
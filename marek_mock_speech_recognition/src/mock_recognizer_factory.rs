@@ -0,0 +1,101 @@
+use marek_speech_recognition_api::{
+    unique_languages, EventReceiver, Recognizer, RecognizerFactory, RecognizerInfo,
+    RecognizerOptions, SpeechError, SpeechResult,
+};
+
+use crate::{MockRecognizer, MockScript};
+
+/// Creates `MockRecognizer`s playing back the script registered for the language.
+pub struct MockRecognizerFactory {
+    scripts: Vec<(String, MockScript)>,
+    is_realtime_only: bool,
+    has_punctuation: bool,
+    supports_command_updates: bool,
+    reports_audio_level: bool,
+}
+
+impl MockRecognizerFactory {
+    pub fn new() -> Self {
+        Self {
+            scripts: Vec::new(),
+            is_realtime_only: false,
+            has_punctuation: true,
+            supports_command_updates: true,
+            reports_audio_level: false,
+        }
+    }
+
+    /// Registers the script for the language.
+    /// Requesting a language without a script results in `NoLanguageFound`.
+    pub fn with_script<T: Into<String>>(mut self, language: T, script: MockScript) -> Self {
+        self.scripts.push((language.into(), script));
+        self
+    }
+
+    /// Makes `write` wait for the real time of the written audio,
+    /// like Google's libsoda does.
+    pub fn with_realtime_only(mut self, is_realtime_only: bool) -> Self {
+        self.is_realtime_only = is_realtime_only;
+        self
+    }
+
+    pub fn with_punctuation(mut self, has_punctuation: bool) -> Self {
+        self.has_punctuation = has_punctuation;
+        self
+    }
+
+    /// With `false`, `set_commands` fails like on the backends that cannot update the commands.
+    pub fn with_command_updates(mut self, supports_command_updates: bool) -> Self {
+        self.supports_command_updates = supports_command_updates;
+        self
+    }
+
+    /// With `true`, `RecognitionEvent::AudioLevel` measured from the written audio
+    /// is sent between the scripted events, at the right sample positions.
+    pub fn with_audio_level(mut self, reports_audio_level: bool) -> Self {
+        self.reports_audio_level = reports_audio_level;
+        self
+    }
+}
+
+impl Default for MockRecognizerFactory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecognizerFactory for MockRecognizerFactory {
+    fn create_recognizer(
+        &mut self,
+        options: RecognizerOptions,
+    ) -> SpeechResult<(Box<dyn Recognizer + Send>, EventReceiver)> {
+        let script = self
+            .scripts
+            .iter()
+            .filter(|(language, _)| *language == options.language)
+            .map(|(_, script)| script.clone())
+            .next()
+            .ok_or(SpeechError::NoLanguageFound(options.language))?;
+
+        let (recognizer, receiver) = MockRecognizer::new(
+            RecognizerInfo {
+                name: "Mock".to_string(),
+                is_realtime_only: self.is_realtime_only,
+                has_punctuation: self.has_punctuation,
+                supports_command_updates: self.supports_command_updates,
+            },
+            options.audio_format,
+            options.event_channel,
+            script,
+            self.reports_audio_level,
+        )?;
+
+        Ok((Box::new(recognizer), receiver))
+    }
+
+    fn available_languages(&self) -> SpeechResult<Vec<String>> {
+        Ok(unique_languages(
+            self.scripts.iter().map(|(language, _)| language),
+        ))
+    }
+}
@@ -0,0 +1,242 @@
+use marek_speech_recognition_api::{EndpointReason, RecognitionEvent, SpeechError};
+
+/// Action executed by the mock recognizer when a scripted step is reached.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockAction {
+    /// Sends the event exactly as given.
+    Event(RecognitionEvent),
+
+    /// Sends `StartOfSpeech` with the time of the step.
+    StartOfSpeech,
+
+    /// Sends `EndOfSpeech` with the time of the step.
+    EndOfSpeech,
+
+    /// Sends `EndOfUtterance` with the time of the step.
+    EndOfUtterance,
+
+    /// Sends `EndOfAudio` with the time of the step.
+    EndOfAudio,
+
+    /// Sends `Recognition` for the audio between `start_sample` and the step.
+    /// Words are spread evenly over that range.
+    Recognition {
+        text: String,
+        is_final: bool,
+        endpoint_reason: Option<EndpointReason>,
+        start_sample: usize,
+    },
+
+    /// Sends `Language` with the given locale.
+    Language(String),
+
+    /// Sends `Error` and returns the error from the `Recognizer` method
+    /// that reached the step.
+    Error(SpeechError),
+}
+
+/// A single scripted step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockStep {
+    /// The step is executed once this number of samples has been written.
+    pub at_sample: usize,
+
+    pub action: MockAction,
+}
+
+/// Sequence of actions played back by `MockRecognizer`.
+///
+/// Steps are keyed to the total number of samples passed to `write`,
+/// so the same script always produces the same events for the same audio
+/// regardless of how it is split into buffers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MockScript {
+    steps: Vec<MockStep>,
+    stop_actions: Vec<MockAction>,
+}
+
+impl MockScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a step. Steps with the same position keep the order they were added in.
+    pub fn step(mut self, at_sample: usize, action: MockAction) -> Self {
+        let index = self
+            .steps
+            .iter()
+            .position(|step| step.at_sample > at_sample)
+            .unwrap_or(self.steps.len());
+        self.steps.insert(index, MockStep { at_sample, action });
+        self
+    }
+
+    /// Adds an action executed by `stop`, before the `Stop` event is sent.
+    pub fn on_stop(mut self, action: MockAction) -> Self {
+        self.stop_actions.push(action);
+        self
+    }
+
+    pub fn event(self, at_sample: usize, event: RecognitionEvent) -> Self {
+        self.step(at_sample, MockAction::Event(event))
+    }
+
+    pub fn start_of_speech(self, at_sample: usize) -> Self {
+        self.step(at_sample, MockAction::StartOfSpeech)
+    }
+
+    pub fn end_of_speech(self, at_sample: usize) -> Self {
+        self.step(at_sample, MockAction::EndOfSpeech)
+    }
+
+    pub fn end_of_utterance(self, at_sample: usize) -> Self {
+        self.step(at_sample, MockAction::EndOfUtterance)
+    }
+
+    pub fn end_of_audio(self, at_sample: usize) -> Self {
+        self.step(at_sample, MockAction::EndOfAudio)
+    }
+
+    /// Adds a partial result starting at the last preceding start of speech.
+    pub fn partial<T: Into<String>>(self, at_sample: usize, text: T) -> Self {
+        self.recognition(at_sample, text.into(), false, None)
+    }
+
+    /// Adds a final result starting at the last preceding start of speech,
+    /// finalized because of the pause in the speech.
+    pub fn final_result<T: Into<String>>(self, at_sample: usize, text: T) -> Self {
+        self.final_result_with_reason(at_sample, text, EndpointReason::EndOfSpeech)
+    }
+
+    /// Adds a final result starting at the last preceding start of speech.
+    pub fn final_result_with_reason<T: Into<String>>(
+        self,
+        at_sample: usize,
+        text: T,
+        endpoint_reason: EndpointReason,
+    ) -> Self {
+        self.recognition(at_sample, text.into(), true, Some(endpoint_reason))
+    }
+
+    pub fn language<T: Into<String>>(self, at_sample: usize, id: T) -> Self {
+        self.step(at_sample, MockAction::Language(id.into()))
+    }
+
+    pub fn error(self, at_sample: usize, error: SpeechError) -> Self {
+        self.step(at_sample, MockAction::Error(error))
+    }
+
+    pub fn steps(&self) -> &[MockStep] {
+        &self.steps
+    }
+
+    pub fn stop_actions(&self) -> &[MockAction] {
+        &self.stop_actions
+    }
+
+    fn recognition(
+        self,
+        at_sample: usize,
+        text: String,
+        is_final: bool,
+        endpoint_reason: Option<EndpointReason>,
+    ) -> Self {
+        let start_sample = self
+            .steps
+            .iter()
+            .rev()
+            .find(|step| step.at_sample <= at_sample && step.action == MockAction::StartOfSpeech)
+            .map(|step| step.at_sample)
+            .unwrap_or(0);
+
+        self.step(
+            at_sample,
+            MockAction::Recognition {
+                text,
+                is_final,
+                endpoint_reason,
+                start_sample,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_are_ordered_by_sample() {
+        let script = MockScript::new()
+            .end_of_speech(300)
+            .start_of_speech(100)
+            .end_of_audio(300)
+            .language(100, "pl");
+
+        let steps = script
+            .steps()
+            .iter()
+            .map(|step| (step.at_sample, step.action.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            steps,
+            vec![
+                (100, MockAction::StartOfSpeech),
+                (100, MockAction::Language("pl".to_string())),
+                (300, MockAction::EndOfSpeech),
+                (300, MockAction::EndOfAudio),
+            ]
+        );
+    }
+
+    #[test]
+    fn recognition_starts_at_last_start_of_speech() {
+        let script = MockScript::new()
+            .start_of_speech(100)
+            .start_of_speech(500)
+            .partial(400, "one")
+            .final_result(800, "two")
+            .start_of_speech(1000);
+
+        let start_samples = script
+            .steps()
+            .iter()
+            .filter_map(|step| match &step.action {
+                MockAction::Recognition {
+                    text, start_sample, ..
+                } => Some((text.as_str(), *start_sample)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(start_samples, vec![("one", 100), ("two", 500)]);
+    }
+
+    #[test]
+    fn recognition_without_start_of_speech_starts_at_zero() {
+        let script =
+            MockScript::new().final_result_with_reason(200, "hello", EndpointReason::EndOfAudio);
+
+        assert_eq!(
+            script.steps()[0].action,
+            MockAction::Recognition {
+                text: "hello".to_string(),
+                is_final: true,
+                endpoint_reason: Some(EndpointReason::EndOfAudio),
+                start_sample: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn stop_actions_keep_their_order() {
+        let script = MockScript::new()
+            .on_stop(MockAction::EndOfSpeech)
+            .on_stop(MockAction::EndOfAudio);
+
+        assert_eq!(
+            script.stop_actions(),
+            &[MockAction::EndOfSpeech, MockAction::EndOfAudio]
+        );
+        assert!(script.steps().is_empty());
+    }
+}
@@ -0,0 +1,10 @@
+mod offline_transducer;
+mod online_transducer;
+mod sherpa_recognizer;
+mod sherpa_recognizer_factory;
+
+pub use sherpa_recognizer::SherpaRecognizer;
+pub use sherpa_recognizer_factory::SherpaModel;
+pub use sherpa_recognizer_factory::SherpaModelInfo;
+pub use sherpa_recognizer_factory::SherpaRecognizerFactory;
+pub use sherpa_recognizer_factory::SherpaTransducerFiles;
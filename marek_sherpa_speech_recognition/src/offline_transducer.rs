@@ -0,0 +1,149 @@
+use std::ffi::{c_char, CStr, CString};
+use std::mem;
+use std::path::Path;
+
+use marek_speech_recognition_api::{SpeechError, SpeechResult};
+use sherpa_rs::sherpa_rs_sys as sys;
+
+use crate::SherpaTransducerFiles;
+
+/// Sample rate of the models published by sherpa-onnx.
+pub(crate) const SHERPA_SAMPLE_RATE: u32 = 16000;
+
+/// Decoded text with its tokens.
+pub(crate) struct TransducerResult {
+    pub text: String,
+    pub tokens: Vec<String>,
+
+    /// Start time of each token, in seconds, if the model supports it.
+    pub timestamps: Option<Vec<f32>>,
+
+    pub language: Option<String>,
+}
+
+/// Safe wrapper of sherpa's non-streaming recognizer.
+pub(crate) struct OfflineTransducer {
+    recognizer: *const sys::SherpaOnnxOfflineRecognizer,
+}
+
+unsafe impl Send for OfflineTransducer {}
+
+impl OfflineTransducer {
+    pub fn new(
+        files: &SherpaTransducerFiles,
+        model_type: &str,
+        feature_dim: i32,
+        threads: i32,
+    ) -> SpeechResult<Self> {
+        let encoder = to_cstring(&files.encoder)?;
+        let decoder = to_cstring(&files.decoder)?;
+        let joiner = to_cstring(&files.joiner)?;
+        let tokens = to_cstring(&files.tokens)?;
+        let model_type = CString::new(model_type)
+            .map_err(|_| SpeechError::LoadLibraryError("Invalid model type".to_string()))?;
+        let provider = CString::new("cpu").unwrap();
+        let decoding_method = CString::new("greedy_search").unwrap();
+
+        let recognizer = unsafe {
+            // null pointers are treated by sherpa as default values
+            let mut config: sys::SherpaOnnxOfflineRecognizerConfig = mem::zeroed();
+            config.feat_config.sample_rate = SHERPA_SAMPLE_RATE as i32;
+            config.feat_config.feature_dim = feature_dim;
+            config.model_config.transducer.encoder = encoder.as_ptr();
+            config.model_config.transducer.decoder = decoder.as_ptr();
+            config.model_config.transducer.joiner = joiner.as_ptr();
+            config.model_config.tokens = tokens.as_ptr();
+            config.model_config.num_threads = threads;
+            config.model_config.provider = provider.as_ptr();
+            config.model_config.model_type = model_type.as_ptr();
+            config.decoding_method = decoding_method.as_ptr();
+
+            sys::SherpaOnnxCreateOfflineRecognizer(&config)
+        };
+
+        if recognizer.is_null() {
+            return Err(SpeechError::LanguageFolderError(files.encoder.clone()));
+        }
+
+        Ok(Self { recognizer })
+    }
+
+    pub fn decode(&mut self, samples: &[f32]) -> SpeechResult<TransducerResult> {
+        unsafe {
+            let stream = sys::SherpaOnnxCreateOfflineStream(self.recognizer);
+            if stream.is_null() {
+                return Err(SpeechError::DecodingFailed(
+                    "Cannot create sherpa stream".to_string(),
+                ));
+            }
+
+            sys::SherpaOnnxAcceptWaveformOffline(
+                stream,
+                SHERPA_SAMPLE_RATE as i32,
+                samples.as_ptr(),
+                samples.len() as i32,
+            );
+            sys::SherpaOnnxDecodeOfflineStream(self.recognizer, stream);
+
+            let result_ptr = sys::SherpaOnnxGetOfflineStreamResult(stream);
+            if result_ptr.is_null() {
+                sys::SherpaOnnxDestroyOfflineStream(stream);
+                return Err(SpeechError::DecodingFailed(
+                    "Cannot get sherpa result".to_string(),
+                ));
+            }
+
+            let raw_result = &*result_ptr;
+            let result = TransducerResult {
+                text: to_string(raw_result.text),
+                tokens: to_tokens(raw_result.tokens_arr, raw_result.count),
+                timestamps: to_timestamps(raw_result.timestamps, raw_result.count),
+                language: Some(to_string(raw_result.lang)).filter(|lang| !lang.is_empty()),
+            };
+
+            sys::SherpaOnnxDestroyOfflineRecognizerResult(result_ptr);
+            sys::SherpaOnnxDestroyOfflineStream(stream);
+
+            Ok(result)
+        }
+    }
+}
+
+impl Drop for OfflineTransducer {
+    fn drop(&mut self) {
+        unsafe {
+            sys::SherpaOnnxDestroyOfflineRecognizer(self.recognizer);
+        }
+    }
+}
+
+pub(crate) fn to_cstring(path: &Path) -> SpeechResult<CString> {
+    path.to_str()
+        .and_then(|path| CString::new(path).ok())
+        .ok_or_else(|| SpeechError::LanguageFolderError(path.to_path_buf()))
+}
+
+pub(crate) unsafe fn to_string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(ptr).to_string_lossy().into_owned()
+    }
+}
+
+pub(crate) unsafe fn to_tokens(tokens: *const *const c_char, count: i32) -> Vec<String> {
+    if tokens.is_null() || count <= 0 {
+        return Vec::new();
+    }
+    std::slice::from_raw_parts(tokens, count as usize)
+        .iter()
+        .map(|&token| to_string(token))
+        .collect()
+}
+
+pub(crate) unsafe fn to_timestamps(timestamps: *const f32, count: i32) -> Option<Vec<f32>> {
+    if timestamps.is_null() || count <= 0 {
+        return None;
+    }
+    Some(std::slice::from_raw_parts(timestamps, count as usize).to_vec())
+}
@@ -0,0 +1,158 @@
+use std::ffi::CString;
+use std::mem;
+
+use marek_speech_recognition_api::{SpeechError, SpeechResult};
+use sherpa_rs::sherpa_rs_sys as sys;
+
+use crate::offline_transducer::{
+    to_cstring, to_string, to_timestamps, to_tokens, TransducerResult, SHERPA_SAMPLE_RATE,
+};
+use crate::SherpaTransducerFiles;
+
+/// Safe wrapper of sherpa's streaming recognizer with a single stream.
+pub(crate) struct OnlineTransducer {
+    recognizer: *const sys::SherpaOnnxOnlineRecognizer,
+
+    /// Never null, so it can be passed to sherpa without checks.
+    stream: *const sys::SherpaOnnxOnlineStream,
+}
+
+unsafe impl Send for OnlineTransducer {}
+
+impl OnlineTransducer {
+    pub fn new(
+        files: &SherpaTransducerFiles,
+        feature_dim: i32,
+        threads: i32,
+    ) -> SpeechResult<Self> {
+        let encoder = to_cstring(&files.encoder)?;
+        let decoder = to_cstring(&files.decoder)?;
+        let joiner = to_cstring(&files.joiner)?;
+        let tokens = to_cstring(&files.tokens)?;
+        let provider = CString::new("cpu").unwrap();
+        let decoding_method = CString::new("greedy_search").unwrap();
+
+        let recognizer = unsafe {
+            // null pointers are treated by sherpa as default values
+            let mut config: sys::SherpaOnnxOnlineRecognizerConfig = mem::zeroed();
+            config.feat_config.sample_rate = SHERPA_SAMPLE_RATE as i32;
+            config.feat_config.feature_dim = feature_dim;
+            config.model_config.transducer.encoder = encoder.as_ptr();
+            config.model_config.transducer.decoder = decoder.as_ptr();
+            config.model_config.transducer.joiner = joiner.as_ptr();
+            config.model_config.tokens = tokens.as_ptr();
+            config.model_config.num_threads = threads;
+            config.model_config.provider = provider.as_ptr();
+            config.decoding_method = decoding_method.as_ptr();
+
+            // the same endpointing rules as in sherpa's examples
+            config.enable_endpoint = 1;
+            config.rule1_min_trailing_silence = 2.4f32;
+            config.rule2_min_trailing_silence = 1.2f32;
+            config.rule3_min_utterance_length = 20f32;
+
+            sys::SherpaOnnxCreateOnlineRecognizer(&config)
+        };
+
+        if recognizer.is_null() {
+            return Err(SpeechError::LanguageFolderError(files.encoder.clone()));
+        }
+
+        let stream = unsafe { sys::SherpaOnnxCreateOnlineStream(recognizer) };
+        if stream.is_null() {
+            unsafe { sys::SherpaOnnxDestroyOnlineRecognizer(recognizer) };
+            return Err(SpeechError::LanguageFolderError(files.encoder.clone()));
+        }
+
+        Ok(Self { recognizer, stream })
+    }
+
+    /// Accepts the samples and decodes everything that is ready.
+    pub fn accept_waveform(&mut self, samples: &[f32]) {
+        unsafe {
+            sys::SherpaOnnxOnlineStreamAcceptWaveform(
+                self.stream,
+                SHERPA_SAMPLE_RATE as i32,
+                samples.as_ptr(),
+                samples.len() as i32,
+            );
+            self.decode();
+        }
+    }
+
+    /// Marks the end of audio and decodes the rest of the stream.
+    pub fn input_finished(&mut self) {
+        unsafe {
+            sys::SherpaOnnxOnlineStreamInputFinished(self.stream);
+            self.decode();
+        }
+    }
+
+    /// Result of the current utterance. Timestamps are relative to its start.
+    pub fn result(&self) -> SpeechResult<TransducerResult> {
+        unsafe {
+            let result_ptr = sys::SherpaOnnxGetOnlineStreamResult(self.recognizer, self.stream);
+            if result_ptr.is_null() {
+                return Err(SpeechError::DecodingFailed(
+                    "Cannot get sherpa result".to_string(),
+                ));
+            }
+
+            let raw_result = &*result_ptr;
+            let result = TransducerResult {
+                text: to_string(raw_result.text),
+                tokens: to_tokens(raw_result.tokens_arr, raw_result.count),
+                timestamps: to_timestamps(raw_result.timestamps, raw_result.count),
+                language: None,
+            };
+
+            sys::SherpaOnnxDestroyOnlineRecognizerResult(result_ptr);
+
+            Ok(result)
+        }
+    }
+
+    pub fn is_endpoint(&self) -> bool {
+        unsafe { sys::SherpaOnnxOnlineStreamIsEndpoint(self.recognizer, self.stream) != 0 }
+    }
+
+    /// Starts a new utterance.
+    pub fn reset(&mut self) {
+        unsafe {
+            sys::SherpaOnnxOnlineStreamReset(self.recognizer, self.stream);
+        }
+    }
+
+    /// Creates a new stream, e.g. after `input_finished`.
+    /// The old stream is kept when the new one cannot be created.
+    pub fn restart(&mut self) -> SpeechResult {
+        unsafe {
+            let stream = sys::SherpaOnnxCreateOnlineStream(self.recognizer);
+            if stream.is_null() {
+                return Err(SpeechError::DecodingFailed(
+                    "Cannot create sherpa stream".to_string(),
+                ));
+            }
+
+            sys::SherpaOnnxDestroyOnlineStream(self.stream);
+            self.stream = stream;
+        }
+
+        Ok(())
+    }
+
+    unsafe fn decode(&mut self) {
+        while sys::SherpaOnnxIsOnlineStreamReady(self.recognizer, self.stream) != 0 {
+            sys::SherpaOnnxDecodeOnlineStream(self.recognizer, self.stream);
+        }
+    }
+}
+
+impl Drop for OnlineTransducer {
+    fn drop(&mut self) {
+        unsafe {
+            sys::SherpaOnnxDestroyOnlineStream(self.stream);
+            sys::SherpaOnnxDestroyOnlineRecognizer(self.recognizer);
+        }
+    }
+}
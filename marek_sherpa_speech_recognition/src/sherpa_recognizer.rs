@@ -0,0 +1,696 @@
+use std::{
+    path::PathBuf,
+    thread::{self, JoinHandle},
+};
+
+use async_trait::async_trait;
+use futures::channel::oneshot;
+use marek_speech_recognition_api::{
+    event_channel, AudioBuffer, AudioConverter, AudioFormat, AudioLevelMeter, EndpointReason,
+    EventChannelOptions, EventFilter, EventReceiver, EventSender, RecognitionEvent,
+    RecognitionMode, Recognizer, RecognizerInfo, RecognizerState, SpeechError, SpeechResult, Word,
+};
+use sherpa_rs::silero_vad::{SileroVad, SileroVadConfig};
+
+use crate::offline_transducer::{OfflineTransducer, TransducerResult, SHERPA_SAMPLE_RATE};
+use crate::online_transducer::OnlineTransducer;
+use crate::SherpaModel;
+
+/// Minimum duration of speech detected by VAD, in seconds.
+const VAD_MIN_SPEECH_DURATION: f32 = 0.25f32;
+
+/// Samples processed by VAD at once.
+const VAD_WINDOW_SIZE: usize = 512;
+
+pub struct SherpaRecognizer {
+    info: RecognizerInfo,
+    model: SherpaModel,
+    threads: i32,
+    converter: AudioConverter,
+    sender: EventSender,
+    state: RecognizerState,
+
+    /// Loaded model, it is owned by the Sherpa thread while the recognizer is started.
+    engine: Option<SherpaEngine>,
+
+    sherpa_thread_sender: Option<std::sync::mpsc::Sender<SherpaRecognizerEvent>>,
+    sherpa_thread_handle: Option<JoinHandle<SherpaEngine>>,
+}
+
+impl SherpaRecognizer {
+    pub(crate) fn new(
+        model: &SherpaModel,
+        has_punctuation: bool,
+        audio_format: AudioFormat,
+        recognition_mode: RecognitionMode,
+        event_channel_options: EventChannelOptions,
+        threads: i32,
+    ) -> SpeechResult<(Self, EventReceiver)> {
+        if recognition_mode != RecognitionMode::Speech {
+            return Err(SpeechError::RecognitionModeNotSupported(format!(
+                "{:?}",
+                recognition_mode
+            )));
+        }
+
+        let (sender, receiver) = event_channel(event_channel_options);
+
+        let engine = SherpaEngine::new(model, threads)?;
+
+        let converter =
+            AudioConverter::new(audio_format, AudioFormat::mono_f32(SHERPA_SAMPLE_RATE))?;
+
+        Ok((
+            SherpaRecognizer {
+                info: RecognizerInfo {
+                    name: "Sherpa ONNX".to_string(),
+                    is_realtime_only: false,
+                    has_punctuation,
+                    supports_command_updates: false,
+                },
+                model: model.clone(),
+                threads,
+                converter,
+                sender,
+                state: RecognizerState::Created,
+                engine: Some(engine),
+                sherpa_thread_sender: None,
+                sherpa_thread_handle: None,
+            },
+            receiver,
+        ))
+    }
+
+    /// Reports that the Sherpa thread is gone. The thread cannot be used anymore
+    /// and the recognizer needs to be stopped and started again.
+    fn worker_died(&mut self) -> SpeechError {
+        self.sherpa_thread_sender = None;
+        let _ = self
+            .sender
+            .send(RecognitionEvent::Error(SpeechError::WorkerDied));
+        SpeechError::WorkerDied
+    }
+
+    /// Sends `Stop` or `Abort` to the Sherpa thread and waits for it to finish.
+    fn stop_thread(&mut self, message: SherpaRecognizerEvent) -> SpeechResult {
+        self.state = RecognizerState::Stopping;
+
+        let handle = match self.sherpa_thread_handle.take() {
+            Some(handle) => handle,
+            None => {
+                self.state = RecognizerState::Stopped;
+                return Ok(());
+            }
+        };
+
+        // the thread may be already dead, it is detected by join below
+        if let Some(sender) = self.sherpa_thread_sender.take() {
+            let _ = sender.send(message);
+        }
+
+        let result = handle.join();
+        self.state = RecognizerState::Stopped;
+
+        match result {
+            Ok(engine) => {
+                self.engine = Some(engine);
+                Ok(())
+            }
+            Err(_) => Err(self.worker_died()),
+        }
+    }
+}
+
+impl Drop for SherpaRecognizer {
+    fn drop(&mut self) {
+        // end the recognition like `abort` does, so the thread doesn't outlive the recognizer
+        // and doesn't decode the buffered audio when nobody can receive the results
+        if let Some(sender) = self.sherpa_thread_sender.take() {
+            let _ = sender.send(SherpaRecognizerEvent::Abort);
+        }
+        if let Some(handle) = self.sherpa_thread_handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+enum SherpaRecognizerEvent {
+    Write(Vec<f32>, oneshot::Sender<SpeechResult>),
+    Stop,
+
+    /// Stops without processing the remaining audio.
+    Abort,
+}
+
+#[async_trait]
+impl Recognizer for SherpaRecognizer {
+    fn info(&self) -> &RecognizerInfo {
+        &self.info
+    }
+
+    fn state(&self) -> RecognizerState {
+        self.state
+    }
+
+    fn subscribe(&self, filter: EventFilter, options: EventChannelOptions) -> EventReceiver {
+        self.sender.subscribe(filter, options)
+    }
+
+    async fn start(&mut self) -> SpeechResult {
+        self.state.check_start()?;
+
+        // the model is lost when the previous thread died, so it is loaded again
+        let engine = match self.engine.take() {
+            Some(engine) => engine,
+            None => SherpaEngine::new(&self.model, self.threads)?,
+        };
+
+        let mut worker = SherpaWorker::new(engine, self.sender.clone())?;
+
+        self.converter.reset();
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.sherpa_thread_sender = Some(sender);
+
+        self.sherpa_thread_handle = Some(thread::spawn(move || {
+            // sending fails only when the receiver is dropped,
+            // in which case nobody is interested in the events anymore
+            let _ = worker.result_sender.send(RecognitionEvent::Start);
+
+            let mut is_aborted = false;
+
+            // the loop ends also when the recognizer is dropped without calling stop
+            while let Ok(event) = receiver.recv() {
+                match event {
+                    SherpaRecognizerEvent::Write(buffer, sender) => {
+                        let _ = sender.send(worker.write(buffer));
+                    }
+                    SherpaRecognizerEvent::Stop => break,
+                    SherpaRecognizerEvent::Abort => {
+                        is_aborted = true;
+                        break;
+                    }
+                }
+            }
+
+            if !is_aborted {
+                let _ = worker.finish();
+            }
+
+            let _ = worker.result_sender.send(RecognitionEvent::Stop);
+
+            worker.engine
+        }));
+        self.state = RecognizerState::Running;
+
+        Ok(())
+    }
+
+    async fn write(&mut self, buffer: AudioBuffer<'_>) -> SpeechResult {
+        self.state.check_write()?;
+        if self.state == RecognizerState::Paused {
+            return Ok(());
+        }
+
+        let sherpa_thread_sender = self
+            .sherpa_thread_sender
+            .as_ref()
+            .ok_or(SpeechError::WorkerDied)?;
+        let buffer = self.converter.convert_f32(buffer)?.into_owned();
+
+        let (finish_sender, finish_receiver) = oneshot::channel::<SpeechResult>();
+        let message = SherpaRecognizerEvent::Write(buffer, finish_sender);
+        if sherpa_thread_sender.send(message).is_err() {
+            return Err(self.worker_died());
+        }
+
+        match finish_receiver.await {
+            Ok(result) => result,
+            Err(_) => Err(self.worker_died()),
+        }
+    }
+
+    async fn stop(&mut self) -> SpeechResult {
+        self.state.check_stop()?;
+        self.stop_thread(SherpaRecognizerEvent::Stop)
+    }
+
+    async fn abort(&mut self) -> SpeechResult {
+        self.state.check_abort()?;
+        self.stop_thread(SherpaRecognizerEvent::Abort)
+    }
+
+    async fn pause(&mut self) -> SpeechResult {
+        self.state.check_pause()?;
+
+        // the Sherpa thread keeps its state and waits for more audio
+        self.state = RecognizerState::Paused;
+        Ok(())
+    }
+
+    async fn resume(&mut self) -> SpeechResult {
+        self.state.check_resume()?;
+        self.state = RecognizerState::Running;
+        Ok(())
+    }
+}
+
+enum SherpaEngine {
+    Offline {
+        transducer: OfflineTransducer,
+        vad_model: PathBuf,
+    },
+    Online {
+        transducer: OnlineTransducer,
+    },
+}
+
+impl SherpaEngine {
+    fn new(model: &SherpaModel, threads: i32) -> SpeechResult<Self> {
+        Ok(match model {
+            SherpaModel::Offline {
+                files,
+                model_type,
+                vad_model,
+                feature_dim,
+            } => SherpaEngine::Offline {
+                transducer: OfflineTransducer::new(files, model_type, *feature_dim, threads)?,
+                vad_model: vad_model.clone(),
+            },
+            SherpaModel::Online { files, feature_dim } => SherpaEngine::Online {
+                transducer: OnlineTransducer::new(files, *feature_dim, threads)?,
+            },
+        })
+    }
+}
+
+/// State owned by the Sherpa thread.
+struct SherpaWorker {
+    engine: SherpaEngine,
+
+    /// Splits the audio into utterances for the offline model.
+    vad: Option<SileroVad>,
+    is_speech: bool,
+
+    /// End of the last segment detected by VAD.
+    segment_end_sample: usize,
+
+    samples_written: usize,
+    utterance_start_sample: usize,
+    last_text: String,
+    last_language: Option<String>,
+    audio_level_meter: AudioLevelMeter,
+    result_sender: EventSender,
+}
+
+impl SherpaWorker {
+    fn new(engine: SherpaEngine, result_sender: EventSender) -> SpeechResult<Self> {
+        let vad = match &engine {
+            SherpaEngine::Offline { vad_model, .. } => {
+                let model = vad_model
+                    .to_str()
+                    .ok_or_else(|| SpeechError::LanguageFolderError(vad_model.clone()))?;
+                let config = SileroVadConfig {
+                    model: model.to_string(),
+                    min_silence_duration: 0.5f32,
+                    min_speech_duration: VAD_MIN_SPEECH_DURATION,
+                    max_speech_duration: 20f32,
+                    sample_rate: SHERPA_SAMPLE_RATE,
+                    window_size: VAD_WINDOW_SIZE as i32,
+                    ..Default::default()
+                };
+                Some(
+                    SileroVad::new(config, 60f32)
+                        .map_err(|_| SpeechError::LanguageFolderError(vad_model.clone()))?,
+                )
+            }
+            SherpaEngine::Online { .. } => None,
+        };
+
+        Ok(Self {
+            engine,
+            vad,
+            is_speech: false,
+            segment_end_sample: 0,
+            samples_written: 0,
+            utterance_start_sample: 0,
+            last_text: String::new(),
+            last_language: None,
+            // sherpa doesn't report the audio level, so it is measured here
+            audio_level_meter: AudioLevelMeter::new(SHERPA_SAMPLE_RATE),
+            result_sender,
+        })
+    }
+
+    /// Decodes the buffer. Errors are also sent as events.
+    fn write(&mut self, buffer: Vec<f32>) -> SpeechResult {
+        for event in self.audio_level_meter.process((&buffer).into()) {
+            let _ = self.result_sender.send(event);
+        }
+
+        self.samples_written += buffer.len();
+
+        let result = match &mut self.engine {
+            SherpaEngine::Offline { .. } => {
+                if let Some(vad) = &mut self.vad {
+                    vad.accept_waveform(buffer);
+                }
+                self.process_speech_segments(EndpointReason::EndOfSpeech)
+            }
+            SherpaEngine::Online { transducer } => {
+                transducer.accept_waveform(&buffer);
+                self.process_online_result()
+            }
+        };
+
+        self.send_error(result)
+    }
+
+    /// Decodes the rest of the audio.
+    fn finish(&mut self) -> SpeechResult {
+        let result = match &mut self.engine {
+            SherpaEngine::Offline { .. } => {
+                if let Some(vad) = &mut self.vad {
+                    vad.flush();
+                }
+                self.process_speech_segments(EndpointReason::EndOfAudio)
+            }
+            SherpaEngine::Online { transducer } => {
+                transducer.input_finished();
+                let result = transducer.result();
+                let restart_result = transducer.restart();
+                result
+                    .map(|result| self.send_final_result(result, EndpointReason::EndOfAudio))
+                    .and(restart_result)
+            }
+        };
+
+        let _ = self.result_sender.send(RecognitionEvent::EndOfAudio {
+            audio_time_usec: Some(to_usec(self.samples_written)),
+        });
+
+        self.send_error(result)
+    }
+
+    fn send_error(&mut self, result: SpeechResult) -> SpeechResult {
+        if let Err(error) = &result {
+            let _ = self
+                .result_sender
+                .send(RecognitionEvent::Error(error.clone()));
+        }
+        result
+    }
+
+    /// Decodes the segments detected by VAD. `endpoint_reason` tells why they have ended.
+    fn process_speech_segments(&mut self, endpoint_reason: EndpointReason) -> SpeechResult {
+        let vad = match &mut self.vad {
+            Some(vad) => vad,
+            None => return Ok(()),
+        };
+
+        let mut segments = Vec::new();
+        while !vad.is_empty() {
+            segments.push(vad.front());
+            vad.pop();
+        }
+        let is_speech = vad.is_speech();
+
+        let mut result = Ok(());
+        for segment in segments {
+            let start_sample = segment.start.max(0) as usize;
+            let end_sample = start_sample + segment.samples.len();
+
+            if !self.is_speech {
+                let _ = self.result_sender.send(RecognitionEvent::StartOfSpeech {
+                    audio_time_usec: Some(to_usec(start_sample)),
+                });
+            }
+            let _ = self.result_sender.send(RecognitionEvent::EndOfSpeech {
+                audio_time_usec: Some(to_usec(end_sample)),
+            });
+            self.is_speech = false;
+            self.segment_end_sample = end_sample;
+
+            if let SherpaEngine::Offline { transducer, .. } = &mut self.engine {
+                // the remaining segments are still decoded when one of them fails
+                match transducer.decode(&segment.samples) {
+                    Ok(transducer_result) => {
+                        self.send_language(transducer_result.language.clone());
+                        self.utterance_start_sample = start_sample;
+                        self.send_recognition(transducer_result, end_sample, Some(endpoint_reason));
+                    }
+                    Err(error) => result = Err(error),
+                }
+            }
+        }
+
+        // the next utterance may have started already, its segment is not available yet
+        if is_speech && !self.is_speech {
+            self.is_speech = true;
+            let start_sample = speech_start_sample(self.samples_written, self.segment_end_sample);
+            let _ = self.result_sender.send(RecognitionEvent::StartOfSpeech {
+                audio_time_usec: Some(to_usec(start_sample)),
+            });
+        }
+
+        result
+    }
+
+    fn process_online_result(&mut self) -> SpeechResult {
+        let transducer = match &mut self.engine {
+            SherpaEngine::Online { transducer } => transducer,
+            SherpaEngine::Offline { .. } => return Ok(()),
+        };
+
+        let result = transducer.result()?;
+        let is_endpoint = transducer.is_endpoint();
+        if is_endpoint {
+            transducer.reset();
+        }
+
+        if is_endpoint {
+            self.send_final_result(result, EndpointReason::EndOfSpeech);
+        } else if result.text != self.last_text {
+            if self.last_text.is_empty() {
+                let _ = self.result_sender.send(RecognitionEvent::StartOfSpeech {
+                    audio_time_usec: Some(to_usec(self.utterance_start_sample)),
+                });
+            }
+            self.last_text = result.text.clone();
+            self.send_recognition(result, self.samples_written, None);
+        }
+
+        Ok(())
+    }
+
+    /// Ends the utterance of the online model.
+    fn send_final_result(&mut self, result: TransducerResult, endpoint_reason: EndpointReason) {
+        if !result.text.trim().is_empty() {
+            if self.last_text.is_empty() {
+                let _ = self.result_sender.send(RecognitionEvent::StartOfSpeech {
+                    audio_time_usec: Some(to_usec(self.utterance_start_sample)),
+                });
+            }
+            let _ = self.result_sender.send(RecognitionEvent::EndOfSpeech {
+                audio_time_usec: Some(to_usec(self.samples_written)),
+            });
+            self.send_recognition(result, self.samples_written, Some(endpoint_reason));
+        }
+
+        self.last_text.clear();
+        self.utterance_start_sample = self.samples_written;
+    }
+
+    /// Sends the final result if `endpoint_reason` is set, otherwise the partial one.
+    fn send_recognition(
+        &mut self,
+        result: TransducerResult,
+        end_sample: usize,
+        endpoint_reason: Option<EndpointReason>,
+    ) {
+        let text = result.text.trim();
+        if text.is_empty() {
+            return;
+        }
+
+        let start_time_usec = to_usec(self.utterance_start_sample);
+        let end_time_usec = to_usec(end_sample);
+        let words = to_words(&result, start_time_usec, end_time_usec);
+
+        let _ = self.result_sender.send(RecognitionEvent::Recognition {
+            text: text.to_string(),
+            is_final: endpoint_reason.is_some(),
+            audio_start_time_usec: Some(
+                words
+                    .as_ref()
+                    .and_then(|words| words.first())
+                    .map(|word| word.start_time_usec)
+                    .unwrap_or(start_time_usec),
+            ),
+            audio_end_time_usec: Some(end_time_usec),
+            words,
+            endpoint_reason,
+            alternatives: Vec::new(),
+            speaker_vector: None,
+        });
+    }
+
+    fn send_language(&mut self, language: Option<String>) {
+        if language.is_some() && language != self.last_language {
+            self.last_language = language.clone();
+            if let Some(id) = language {
+                let _ = self.result_sender.send(RecognitionEvent::Language {
+                    id,
+                    confidence_level: None,
+                });
+            }
+        }
+    }
+}
+
+/// Merges tokens into words. Sherpa doesn't provide confidence of tokens.
+fn to_words(
+    result: &TransducerResult,
+    start_time_usec: u64,
+    end_time_usec: u64,
+) -> Option<Vec<Word>> {
+    let timestamps = result.timestamps.as_ref()?;
+    let mut words: Vec<Word> = Vec::new();
+    let mut is_word_pending = false;
+
+    for (token, timestamp) in result.tokens.iter().zip(timestamps) {
+        let time_usec = start_time_usec + (timestamp.max(0f32) * 1000000f32) as u64;
+
+        // BPE tokens are parts of words, a new word starts with a space
+        // (or "▁" when the model's tokens are not converted)
+        let is_new_word = token.starts_with(' ') || token.starts_with('▁');
+        let token = token.trim_start_matches([' ', '▁']);
+        if token.is_empty() {
+            // a separate "▁" token starts the word of the next token
+            is_word_pending |= is_new_word;
+            continue;
+        }
+        let is_new_word = is_new_word || std::mem::take(&mut is_word_pending);
+
+        match words.last_mut() {
+            Some(word) if !is_new_word => {
+                word.word.push_str(token);
+            }
+            _ => {
+                if let Some(word) = words.last_mut() {
+                    word.end_time_usec = time_usec;
+                }
+                words.push(Word {
+                    conf: 1f32,
+                    start_time_usec: time_usec,
+                    end_time_usec,
+                    word: token.to_string(),
+                });
+            }
+        }
+    }
+
+    Some(words)
+}
+
+/// Estimates the start of the speech detected by VAD, like VAD computes the start
+/// of the segment: the speech lasted the minimum duration before it was detected.
+fn speech_start_sample(samples_written: usize, segment_end_sample: usize) -> usize {
+    let detection_delay =
+        (VAD_MIN_SPEECH_DURATION * SHERPA_SAMPLE_RATE as f32) as usize + 2 * VAD_WINDOW_SIZE;
+    samples_written
+        .saturating_sub(detection_delay)
+        .max(segment_end_sample)
+}
+
+fn to_usec(samples: usize) -> u64 {
+    samples as u64 * 1000000u64 / SHERPA_SAMPLE_RATE as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(tokens: &[&str], timestamps: Option<Vec<f32>>) -> TransducerResult {
+        TransducerResult {
+            text: tokens.concat(),
+            tokens: tokens.iter().map(|token| token.to_string()).collect(),
+            timestamps,
+            language: None,
+        }
+    }
+
+    fn word(word: &str, start_time_usec: u64, end_time_usec: u64) -> Word {
+        Word {
+            conf: 1f32,
+            start_time_usec,
+            end_time_usec,
+            word: word.to_string(),
+        }
+    }
+
+    #[test]
+    fn converts_samples_to_usec() {
+        assert_eq!(to_usec(0), 0);
+        assert_eq!(to_usec(16), 1000);
+        assert_eq!(to_usec(SHERPA_SAMPLE_RATE as usize * 3), 3000000);
+    }
+
+    #[test]
+    fn merges_tokens_into_words() {
+        let result = result(
+            &[" hel", "lo", " wor", "ld"],
+            Some(vec![0.1f32, 0.2f32, 0.5f32, 0.6f32]),
+        );
+
+        assert_eq!(
+            to_words(&result, 1000000, 2000000),
+            Some(vec![
+                word("hello", 1100000, 1500000),
+                word("world", 1500000, 2000000),
+            ])
+        );
+    }
+
+    #[test]
+    fn merges_sentencepiece_tokens() {
+        let result = result(
+            &["▁good", "▁mor", "ning", "▁", "!"],
+            Some(vec![0f32, 0.3f32, 0.4f32, 0.7f32, 0.8f32]),
+        );
+
+        // the separate "▁" starts the next word
+        assert_eq!(
+            to_words(&result, 0, 1000000),
+            Some(vec![
+                word("good", 0, 300000),
+                word("morning", 300000, 800000),
+                word("!", 800000, 1000000),
+            ])
+        );
+    }
+
+    #[test]
+    fn first_token_starts_a_word() {
+        let result = result(&["he", "llo"], Some(vec![-0.1f32, 0.2f32]));
+
+        assert_eq!(
+            to_words(&result, 500000, 900000),
+            Some(vec![word("hello", 500000, 900000)])
+        );
+    }
+
+    #[test]
+    fn no_words_without_timestamps() {
+        assert_eq!(to_words(&result(&[" hello"], None), 0, 1000000), None);
+    }
+
+    #[test]
+    fn speech_starts_before_its_detection() {
+        let detection_delay = 16000 / 4 + 2 * VAD_WINDOW_SIZE;
+
+        assert_eq!(speech_start_sample(48000, 0), 48000 - detection_delay);
+        assert_eq!(speech_start_sample(1000, 0), 0);
+        assert_eq!(speech_start_sample(48000, 47000), 47000);
+    }
+}
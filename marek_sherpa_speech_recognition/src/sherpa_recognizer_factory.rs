@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+
+use marek_speech_recognition_api::{
+    unique_languages, EventReceiver, Recognizer, RecognizerFactory, RecognizerOptions, SpeechError,
+    SpeechResult,
+};
+
+use crate::SherpaRecognizer;
+
+pub struct SherpaRecognizerFactory {
+    models: Vec<SherpaModelInfo>,
+    threads: i32,
+}
+
+pub struct SherpaModelInfo {
+    pub language: String,
+    pub model: SherpaModel,
+
+    /// Does the model output punctuation (e.g. Parakeet does).
+    pub has_punctuation: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum SherpaModel {
+    /// Non-streaming transducer, e.g. NeMo Parakeet.
+    /// Audio is split into utterances by Silero VAD and only final results are produced.
+    Offline {
+        files: SherpaTransducerFiles,
+
+        /// Sherpa's model type, e.g. "nemo_transducer" for Parakeet.
+        /// Empty string lets sherpa detect it from the model metadata.
+        model_type: String,
+
+        /// Path to `silero_vad.onnx`.
+        vad_model: PathBuf,
+
+        /// Number of mel bins of the model input, e.g. 128 for Parakeet v3.
+        feature_dim: i32,
+    },
+
+    /// Streaming transducer, e.g. Zipformer. Produces partial results.
+    Online {
+        files: SherpaTransducerFiles,
+
+        /// Number of mel bins of the model input, usually 80.
+        feature_dim: i32,
+    },
+}
+
+/// Files of the transducer model, as in the model archives published by sherpa-onnx.
+#[derive(Debug, Clone)]
+pub struct SherpaTransducerFiles {
+    pub encoder: PathBuf,
+    pub decoder: PathBuf,
+    pub joiner: PathBuf,
+    pub tokens: PathBuf,
+}
+
+impl SherpaRecognizerFactory {
+    pub fn new(models: Vec<SherpaModelInfo>) -> SpeechResult<Self> {
+        Ok(Self { models, threads: 1 })
+    }
+
+    /// Sets the number of CPU threads used by the ONNX runtime.
+    pub fn with_threads(mut self, threads: i32) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+}
+
+impl RecognizerFactory for SherpaRecognizerFactory {
+    fn create_recognizer(
+        &mut self,
+        options: RecognizerOptions,
+    ) -> SpeechResult<(Box<dyn Recognizer + Send>, EventReceiver)> {
+        let model_info = self
+            .models
+            .iter()
+            .find(|el| el.language == options.language)
+            .ok_or(SpeechError::NoLanguageFound(options.language.clone()))?;
+
+        let (recognizer, receiver) = SherpaRecognizer::new(
+            &model_info.model,
+            model_info.has_punctuation,
+            options.audio_format,
+            options.mode,
+            options.event_channel,
+            self.threads,
+        )?;
+
+        Ok((Box::new(recognizer), receiver))
+    }
+
+    fn available_languages(&self) -> SpeechResult<Vec<String>> {
+        Ok(unique_languages(
+            self.models.iter().map(|model| &model.language),
+        ))
+    }
+}
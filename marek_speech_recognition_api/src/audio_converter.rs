@@ -0,0 +1,426 @@
+use std::borrow::Cow;
+use std::f64::consts::PI;
+
+use crate::{AudioBuffer, AudioFormat, SampleFormat, SpeechError, SpeechResult};
+
+/// Half of the length of the resampling filter, in zero crossings of the sinc.
+const FILTER_ZERO_CROSSINGS: f64 = 16f64;
+
+/// Cutoff of the resampling filter relative to the lower of the two Nyquist frequencies,
+/// leaving room for the transition band.
+const FILTER_CUTOFF: f64 = 0.9f64;
+
+/// Number of precomputed filter values per input sample.
+const FILTER_TABLE_RESOLUTION: f64 = 256f64;
+
+/// Converts audio written by the user to the format needed by the backend.
+///
+/// Downmixes all channels to mono, converts the sample type and resamples
+/// with a windowed-sinc low-pass filter, so the frequencies above the output's
+/// Nyquist frequency don't alias. The converter keeps the state between calls,
+/// so the audio stream can be split into buffers of any size.
+pub struct AudioConverter {
+    input: AudioFormat,
+    output: AudioFormat,
+    resampler: Option<Resampler>,
+}
+
+impl AudioConverter {
+    /// Creates the converter. The output format needs to be mono.
+    pub fn new(input: AudioFormat, output: AudioFormat) -> SpeechResult<Self> {
+        if input.channels == 0 || input.sample_rate == 0 {
+            return Err(SpeechError::AudioFormatError(format!(
+                "Invalid input format: {:?}",
+                input
+            )));
+        }
+        if output.channels != 1 || output.sample_rate == 0 {
+            return Err(SpeechError::AudioFormatError(format!(
+                "Invalid output format: {:?}",
+                output
+            )));
+        }
+
+        let resampler = if input.sample_rate != output.sample_rate {
+            Some(Resampler::new(input.sample_rate, output.sample_rate))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            input,
+            output,
+            resampler,
+        })
+    }
+
+    pub fn input_format(&self) -> AudioFormat {
+        self.input
+    }
+
+    pub fn output_format(&self) -> AudioFormat {
+        self.output
+    }
+
+    /// Forgets the state of the stream, e.g. when the recognition is restarted.
+    pub fn reset(&mut self) {
+        if let Some(resampler) = &mut self.resampler {
+            resampler.reset();
+        }
+    }
+
+    /// Converts the buffer to mono `i16` samples.
+    /// The output format needs to be `SampleFormat::I16`.
+    pub fn convert_i16<'a>(&mut self, buffer: AudioBuffer<'a>) -> SpeechResult<Cow<'a, [i16]>> {
+        self.check_formats(&buffer, SampleFormat::I16)?;
+
+        if let AudioBuffer::I16(samples) = buffer {
+            if self.is_passthrough() {
+                return Ok(Cow::Borrowed(samples));
+            }
+        }
+
+        Ok(Cow::Owned(
+            self.convert(buffer)
+                .into_iter()
+                .map(|sample| {
+                    (sample * I16_SCALE)
+                        .round()
+                        .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+                })
+                .collect(),
+        ))
+    }
+
+    /// Converts the buffer to mono `f32` samples.
+    /// The output format needs to be `SampleFormat::F32`.
+    pub fn convert_f32<'a>(&mut self, buffer: AudioBuffer<'a>) -> SpeechResult<Cow<'a, [f32]>> {
+        self.check_formats(&buffer, SampleFormat::F32)?;
+
+        if let AudioBuffer::F32(samples) = buffer {
+            if self.is_passthrough() {
+                return Ok(Cow::Borrowed(samples));
+            }
+        }
+
+        Ok(Cow::Owned(self.convert(buffer)))
+    }
+
+    fn check_formats(
+        &self,
+        buffer: &AudioBuffer,
+        output_sample_format: SampleFormat,
+    ) -> SpeechResult {
+        if buffer.sample_format() != self.input.sample_format {
+            return Err(SpeechError::AudioFormatError(format!(
+                "Expected {:?} samples, got {:?}",
+                self.input.sample_format,
+                buffer.sample_format()
+            )));
+        }
+        if self.output.sample_format != output_sample_format {
+            return Err(SpeechError::AudioFormatError(format!(
+                "Output format is {:?}, not {:?}",
+                self.output.sample_format, output_sample_format
+            )));
+        }
+        if !buffer.len().is_multiple_of(self.input.channels as usize) {
+            return Err(SpeechError::AudioFormatError(format!(
+                "Buffer length {} is not a multiple of {} channels",
+                buffer.len(),
+                self.input.channels
+            )));
+        }
+        Ok(())
+    }
+
+    fn is_passthrough(&self) -> bool {
+        self.input.channels == 1 && self.input.sample_rate == self.output.sample_rate
+    }
+
+    fn convert(&mut self, buffer: AudioBuffer) -> Vec<f32> {
+        let mono = self.downmix(buffer);
+
+        match &mut self.resampler {
+            Some(resampler) => resampler.process(&mono),
+            None => mono,
+        }
+    }
+
+    fn downmix(&self, buffer: AudioBuffer) -> Vec<f32> {
+        let channels = self.input.channels as usize;
+        match buffer {
+            AudioBuffer::I16(samples) => samples
+                .chunks_exact(channels)
+                .map(|frame| {
+                    frame.iter().map(|&s| s as f32).sum::<f32>() / (channels as f32 * I16_SCALE)
+                })
+                .collect(),
+            AudioBuffer::F32(samples) => samples
+                .chunks_exact(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+                .collect(),
+        }
+    }
+}
+
+/// Scale between `i16` and `f32` samples, the same in both directions.
+const I16_SCALE: f32 = 32768f32;
+
+/// Streaming windowed-sinc resampler.
+struct Resampler {
+    /// Distance between the output samples, in input samples.
+    step: f64,
+
+    /// Half of the filter length, in input samples.
+    half_width: f64,
+
+    /// Filter values from the center to `half_width`, see `FILTER_TABLE_RESOLUTION`.
+    filter: Vec<f32>,
+
+    /// Input samples still needed by the filter.
+    history: Vec<f32>,
+
+    /// Time of the next output sample, in input samples relative to `history`.
+    position: f64,
+}
+
+impl Resampler {
+    fn new(input_rate: u32, output_rate: u32) -> Self {
+        let step = input_rate as f64 / output_rate as f64;
+
+        // when downsampling, the cutoff is the output's Nyquist frequency
+        let cutoff = FILTER_CUTOFF * (1f64 / step).min(1f64);
+        let half_width = FILTER_ZERO_CROSSINGS / cutoff;
+
+        let table_len = (half_width * FILTER_TABLE_RESOLUTION).ceil() as usize + 2;
+        let filter = (0..table_len)
+            .map(|i| {
+                let distance = i as f64 / FILTER_TABLE_RESOLUTION;
+                (cutoff * sinc(cutoff * distance) * blackman(distance / half_width)) as f32
+            })
+            .collect();
+
+        let mut resampler = Self {
+            step,
+            half_width,
+            filter,
+            history: Vec::new(),
+            position: 0f64,
+        };
+        resampler.reset();
+        resampler
+    }
+
+    fn reset(&mut self) {
+        // the stream starts with silence, so the first output sample is at the first input one
+        let padding = self.half_width.ceil() as usize;
+        self.history.clear();
+        self.history.resize(padding, 0f32);
+        self.position = padding as f64;
+    }
+
+    fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        self.history.extend_from_slice(samples);
+
+        let mut output = Vec::with_capacity((samples.len() as f64 / self.step).ceil() as usize + 1);
+
+        // the filter needs the samples up to `half_width` after the output sample
+        while self.position + self.half_width < (self.history.len() - 1) as f64 {
+            let first = (self.position - self.half_width).ceil() as usize;
+            let last = (self.position + self.half_width).floor() as usize;
+
+            let value = self.history[first..=last]
+                .iter()
+                .enumerate()
+                .map(|(i, &sample)| sample * self.filter_at(self.position - (first + i) as f64))
+                .sum();
+            output.push(value);
+
+            self.position += self.step;
+        }
+
+        let consumed = ((self.position - self.half_width).floor() as usize).min(self.history.len());
+        self.history.drain(..consumed);
+        self.position -= consumed as f64;
+
+        output
+    }
+
+    fn filter_at(&self, distance: f64) -> f32 {
+        let index = distance.abs() * FILTER_TABLE_RESOLUTION;
+        let i = index as usize;
+        if i + 1 >= self.filter.len() {
+            return 0f32;
+        }
+
+        let fraction = (index - i as f64) as f32;
+        self.filter[i] * (1f32 - fraction) + self.filter[i + 1] * fraction
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0f64 {
+        1f64
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Blackman window for `x` in -1..1.
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1f64 {
+        0f64
+    } else {
+        0.42f64 + 0.5f64 * (PI * x).cos() + 0.08f64 * (2f64 * PI * x).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| {
+                (2f32 * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32).sin()
+            })
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn passthrough_borrows_the_buffer() {
+        let mut converter =
+            AudioConverter::new(AudioFormat::mono_i16(16000), AudioFormat::mono_i16(16000))
+                .unwrap();
+        let samples = vec![1i16, -2, 3];
+
+        let output = converter.convert_i16((&samples).into()).unwrap();
+        assert!(matches!(output, Cow::Borrowed(_)));
+        assert_eq!(&*output, &samples[..]);
+    }
+
+    #[test]
+    fn downmixes_channels() {
+        let mut converter = AudioConverter::new(
+            AudioFormat::new(SampleFormat::I16, 2, 16000),
+            AudioFormat::mono_i16(16000),
+        )
+        .unwrap();
+
+        let samples = vec![1000i16, 3000, -100, 100];
+        let output = converter.convert_i16((&samples).into()).unwrap();
+        assert_eq!(&*output, &[2000i16, 0]);
+    }
+
+    #[test]
+    fn rejects_incomplete_frames() {
+        let mut converter = AudioConverter::new(
+            AudioFormat::new(SampleFormat::F32, 2, 16000),
+            AudioFormat::mono_f32(16000),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            converter.convert_f32((&vec![0f32; 3]).into()),
+            Err(SpeechError::AudioFormatError(_))
+        ));
+    }
+
+    #[test]
+    fn sample_scaling_is_symmetric() {
+        let samples = vec![i16::MIN, -12345, -1, 0, 1, 12345, i16::MAX];
+
+        let mut to_f32 =
+            AudioConverter::new(AudioFormat::mono_i16(16000), AudioFormat::mono_f32(16000))
+                .unwrap();
+        let floats = to_f32.convert_f32((&samples).into()).unwrap().into_owned();
+        assert_eq!(floats[0], -1f32);
+        assert_eq!(floats[3], 0f32);
+
+        let mut to_i16 =
+            AudioConverter::new(AudioFormat::mono_f32(16000), AudioFormat::mono_i16(16000))
+                .unwrap();
+        assert_eq!(
+            &*to_i16.convert_i16((&floats).into()).unwrap(),
+            &samples[..]
+        );
+
+        // out of range values are clamped
+        assert_eq!(
+            &*to_i16.convert_i16((&vec![-2f32, 2f32]).into()).unwrap(),
+            &[i16::MIN, i16::MAX]
+        );
+    }
+
+    #[test]
+    fn resampling_keeps_state_between_buffers() {
+        let input = sine(440f32, 44100, 44100);
+
+        let mut converter =
+            AudioConverter::new(AudioFormat::mono_f32(44100), AudioFormat::mono_f32(16000))
+                .unwrap();
+        let whole = converter.convert_f32((&input).into()).unwrap().into_owned();
+
+        converter.reset();
+        let mut split = Vec::new();
+        for chunk in input.chunks(1234) {
+            split.extend_from_slice(&converter.convert_f32(chunk.into()).unwrap());
+        }
+
+        assert_eq!(whole.len(), split.len());
+        for (sample1, sample2) in whole.iter().zip(&split) {
+            assert!((sample1 - sample2).abs() < 1e-4);
+        }
+
+        // only the filter's delay is missing at the end
+        assert!(whole.len() <= 16000 && whole.len() > 15900);
+    }
+
+    #[test]
+    fn downsampling_keeps_the_passband() {
+        let input = sine(1000f32, 48000, 48000);
+
+        let mut converter =
+            AudioConverter::new(AudioFormat::mono_f32(48000), AudioFormat::mono_f32(16000))
+                .unwrap();
+        let output = converter.convert_f32((&input).into()).unwrap();
+
+        // skip the start with the silence before the stream
+        let expected = sine(1000f32, 16000, output.len());
+        for (sample, expected) in output.iter().zip(&expected).skip(100) {
+            assert!((sample - expected).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn downsampling_filters_out_aliases() {
+        // above 8 kHz, the Nyquist frequency of the output
+        let input = sine(12000f32, 48000, 48000);
+
+        let mut converter =
+            AudioConverter::new(AudioFormat::mono_f32(48000), AudioFormat::mono_f32(16000))
+                .unwrap();
+        let output = converter.convert_f32((&input).into()).unwrap();
+
+        assert!(rms(&output[100..]) < 0.01);
+    }
+
+    #[test]
+    fn upsampling_interpolates() {
+        let input = sine(500f32, 8000, 8000);
+
+        let mut converter =
+            AudioConverter::new(AudioFormat::mono_f32(8000), AudioFormat::mono_f32(16000)).unwrap();
+        let output = converter.convert_f32((&input).into()).unwrap();
+
+        let expected = sine(500f32, 16000, output.len());
+        for (sample, expected) in output.iter().zip(&expected).skip(100) {
+            assert!((sample - expected).abs() < 0.01);
+        }
+    }
+}
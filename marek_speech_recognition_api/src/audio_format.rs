@@ -0,0 +1,98 @@
+/// Type of a single PCM sample.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    /// Signed 16-bit integer.
+    I16,
+
+    /// 32-bit float in range -1.0..1.0.
+    F32,
+}
+
+/// Layout of the PCM audio passed to `Recognizer::write`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioFormat {
+    pub sample_format: SampleFormat,
+
+    /// Number of interleaved channels.
+    pub channels: u16,
+
+    /// Sample rate, in Hz.
+    pub sample_rate: u32,
+}
+
+impl AudioFormat {
+    pub fn new(sample_format: SampleFormat, channels: u16, sample_rate: u32) -> Self {
+        Self {
+            sample_format,
+            channels,
+            sample_rate,
+        }
+    }
+
+    pub fn mono_i16(sample_rate: u32) -> Self {
+        Self::new(SampleFormat::I16, 1, sample_rate)
+    }
+
+    pub fn mono_f32(sample_rate: u32) -> Self {
+        Self::new(SampleFormat::F32, 1, sample_rate)
+    }
+}
+
+impl Default for AudioFormat {
+    fn default() -> Self {
+        Self::mono_i16(16000)
+    }
+}
+
+/// Chunk of interleaved PCM samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioBuffer<'a> {
+    I16(&'a [i16]),
+    F32(&'a [f32]),
+}
+
+impl<'a> AudioBuffer<'a> {
+    pub fn sample_format(&self) -> SampleFormat {
+        match self {
+            AudioBuffer::I16(_) => SampleFormat::I16,
+            AudioBuffer::F32(_) => SampleFormat::F32,
+        }
+    }
+
+    /// Number of samples of all channels.
+    pub fn len(&self) -> usize {
+        match self {
+            AudioBuffer::I16(buffer) => buffer.len(),
+            AudioBuffer::F32(buffer) => buffer.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<'a> From<&'a [i16]> for AudioBuffer<'a> {
+    fn from(buffer: &'a [i16]) -> Self {
+        AudioBuffer::I16(buffer)
+    }
+}
+
+impl<'a> From<&'a [f32]> for AudioBuffer<'a> {
+    fn from(buffer: &'a [f32]) -> Self {
+        AudioBuffer::F32(buffer)
+    }
+}
+
+impl<'a> From<&'a Vec<i16>> for AudioBuffer<'a> {
+    fn from(buffer: &'a Vec<i16>) -> Self {
+        AudioBuffer::I16(buffer)
+    }
+}
+
+impl<'a> From<&'a Vec<f32>> for AudioBuffer<'a> {
+    fn from(buffer: &'a Vec<f32>) -> Self {
+        AudioBuffer::F32(buffer)
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{AudioBuffer, RecognitionEvent};
+
+/// How often the level is reported (100 ms).
+const INTERVAL_MSEC: u64 = 100;
+
+/// Weight of the new measurement in the low-pass filtered RMS.
+const RMS_SMOOTHING: f32 = 0.5f32;
+
+/// Loudness mapped to level 0, in dBFS. Level 1 is 0 dBFS.
+const MIN_LEVEL_DB: f32 = -60f32;
+
+/// Computes `RecognitionEvent::AudioLevel` from mono audio, for the backends
+/// that don't report the level by themselves.
+pub struct AudioLevelMeter {
+    sample_rate: u32,
+    interval_samples: usize,
+
+    /// Low-pass filtered RMS of the previous intervals.
+    rms: f32,
+
+    /// Sum of squares of the samples in the current interval.
+    sum_of_squares: f32,
+    interval_position: usize,
+
+    samples_processed: u64,
+}
+
+impl AudioLevelMeter {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            interval_samples: ((sample_rate as u64 * INTERVAL_MSEC / 1000u64) as usize).max(1),
+            rms: 0f32,
+            sum_of_squares: 0f32,
+            interval_position: 0,
+            samples_processed: 0,
+        }
+    }
+
+    /// Maps RMS in range 0..1 to the loudness reported as `RecognitionEvent::AudioLevel::level`.
+    pub fn level_of(rms: f32) -> f32 {
+        if rms <= 0f32 {
+            return 0f32;
+        }
+        let db = 20f32 * rms.log10();
+        ((db - MIN_LEVEL_DB) / -MIN_LEVEL_DB).clamp(0f32, 1f32)
+    }
+
+    /// Forgets the state of the stream, e.g. when the recognition is restarted.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.sample_rate);
+    }
+
+    /// Measures the mono samples. Returns the event for each finished interval.
+    pub fn process(&mut self, buffer: AudioBuffer<'_>) -> Vec<RecognitionEvent> {
+        let mut events = Vec::new();
+
+        let samples: Box<dyn Iterator<Item = f32>> = match buffer {
+            AudioBuffer::I16(samples) => {
+                Box::new(samples.iter().map(|&sample| sample as f32 / 32768f32))
+            }
+            AudioBuffer::F32(samples) => Box::new(samples.iter().copied()),
+        };
+
+        for sample in samples {
+            self.sum_of_squares += sample * sample;
+            self.interval_position += 1;
+            self.samples_processed += 1;
+
+            if self.interval_position == self.interval_samples {
+                let rms = (self.sum_of_squares / self.interval_samples as f32)
+                    .sqrt()
+                    .min(1f32);
+                self.rms += (rms - self.rms) * RMS_SMOOTHING;
+                self.sum_of_squares = 0f32;
+                self.interval_position = 0;
+
+                events.push(RecognitionEvent::AudioLevel {
+                    rms: self.rms,
+                    level: Self::level_of(self.rms),
+                    speech_likelihood: None,
+                    audio_time_usec: Some(
+                        self.samples_processed * 1000000u64 / self.sample_rate as u64,
+                    ),
+                });
+            }
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(events: &[RecognitionEvent]) -> Vec<(f32, f32, Option<u64>)> {
+        events
+            .iter()
+            .map(|event| match event {
+                RecognitionEvent::AudioLevel {
+                    rms,
+                    level,
+                    speech_likelihood: None,
+                    audio_time_usec,
+                } => (*rms, *level, *audio_time_usec),
+                event => panic!("unexpected event {:?}", event),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn level_is_logarithmic() {
+        assert_eq!(AudioLevelMeter::level_of(0f32), 0f32);
+        assert_eq!(AudioLevelMeter::level_of(0.0001f32), 0f32);
+        assert!((AudioLevelMeter::level_of(0.001f32) - 0f32).abs() < 1e-6);
+        assert!((AudioLevelMeter::level_of(0.01f32) - 1f32 / 3f32).abs() < 1e-6);
+        assert!((AudioLevelMeter::level_of(0.1f32) - 2f32 / 3f32).abs() < 1e-6);
+        assert_eq!(AudioLevelMeter::level_of(1f32), 1f32);
+    }
+
+    #[test]
+    fn reports_every_interval_with_smoothing() {
+        let mut meter = AudioLevelMeter::new(16000);
+
+        // 1.5 intervals, the second one is finished by the next buffer
+        let events = meter.process((&vec![0.5f32; 2400]).into());
+        assert_eq!(
+            levels(&events),
+            vec![(0.25f32, AudioLevelMeter::level_of(0.25f32), Some(100000))]
+        );
+
+        let events = meter.process((&vec![0.5f32; 800]).into());
+        let levels = levels(&events);
+        assert_eq!(levels.len(), 1);
+        assert_eq!(levels[0].0, 0.375f32);
+        assert_eq!(levels[0].2, Some(200000));
+    }
+
+    #[test]
+    fn i16_and_f32_samples_are_measured_the_same() {
+        let mut i16_meter = AudioLevelMeter::new(8000);
+        let mut f32_meter = AudioLevelMeter::new(8000);
+
+        let i16_samples = vec![16384i16; 800];
+        let f32_samples = vec![0.5f32; 800];
+
+        assert_eq!(
+            levels(&i16_meter.process((&i16_samples).into())),
+            levels(&f32_meter.process((&f32_samples).into()))
+        );
+    }
+
+    #[test]
+    fn reset_forgets_the_stream() {
+        let mut meter = AudioLevelMeter::new(16000);
+        meter.process((&vec![1f32; 2000]).into());
+
+        meter.reset();
+
+        let events = meter.process((&vec![1f32; 1600]).into());
+        assert_eq!(
+            levels(&events),
+            vec![(0.5f32, AudioLevelMeter::level_of(0.5f32), Some(100000))]
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use crate::RecognitionEvent;
+
+/// Matches the final results against the commands of `RecognitionMode::Commands`
+/// and turns them into `RecognitionEvent::Command` or `RecognitionEvent::NoMatch`.
+///
+/// The text matches a command if they are the same ignoring the case, whitespace
+/// and the punctuation around the words (e.g. Google's formatted "Turn on the light.").
+/// Words in square brackets, like Vosk's `[unk]`, mark the speech out of the commands.
+pub struct CommandMatcher {
+    commands: Vec<String>,
+    normalized_commands: Vec<String>,
+    confidence_threshold: f32,
+}
+
+impl CommandMatcher {
+    pub fn new(commands: Vec<String>, confidence_threshold: f32) -> Self {
+        let mut command_matcher = Self {
+            commands: Vec::new(),
+            normalized_commands: Vec::new(),
+            confidence_threshold,
+        };
+        command_matcher.set_commands(commands);
+        command_matcher
+    }
+
+    pub fn set_commands(&mut self, commands: Vec<String>) {
+        self.normalized_commands = commands.iter().map(|command| normalize(command)).collect();
+        self.commands = commands;
+    }
+
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+
+    /// Returns the event for the final result with the given confidence (0..1).
+    pub fn process(&self, text: &str, confidence: f32) -> RecognitionEvent {
+        let has_unknown_words = text.split_whitespace().any(is_unknown_word);
+        let text = text
+            .split_whitespace()
+            .filter(|word| !is_unknown_word(word))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if !has_unknown_words && confidence >= self.confidence_threshold {
+            let normalized_text = normalize(&text);
+            if let Some(index) = self
+                .normalized_commands
+                .iter()
+                .position(|command| *command == normalized_text)
+            {
+                return RecognitionEvent::Command {
+                    index,
+                    phrase: self.commands[index].clone(),
+                    confidence,
+                };
+            }
+        }
+
+        RecognitionEvent::NoMatch { text, confidence }
+    }
+}
+
+fn normalize(text: &str) -> String {
+    // the punctuation inside the words, like in "don't", is kept
+    text.split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_unknown_word(word: &str) -> bool {
+    word.starts_with('[') && word.ends_with(']')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands() -> Vec<String> {
+        vec!["Turn on".to_string(), "turn  off the light".to_string()]
+    }
+
+    #[test]
+    fn ignores_case_and_whitespace() {
+        let command_matcher = CommandMatcher::new(commands(), 0f32);
+
+        assert_eq!(
+            command_matcher.process("turn ON", 0.5f32),
+            RecognitionEvent::Command {
+                index: 0,
+                phrase: "Turn on".to_string(),
+                confidence: 0.5f32,
+            }
+        );
+        assert_eq!(
+            command_matcher.process("  Turn off\tthe   light ", 1f32),
+            RecognitionEvent::Command {
+                index: 1,
+                phrase: "turn  off the light".to_string(),
+                confidence: 1f32,
+            }
+        );
+        assert_eq!(
+            command_matcher.process("turn on the light", 1f32),
+            RecognitionEvent::NoMatch {
+                text: "turn on the light".to_string(),
+                confidence: 1f32,
+            }
+        );
+    }
+
+    #[test]
+    fn ignores_punctuation_around_words() {
+        let command_matcher = CommandMatcher::new(
+            vec!["Turn on the light".to_string(), "don't stop".to_string()],
+            0f32,
+        );
+
+        assert!(matches!(
+            command_matcher.process("Turn on the light.", 1f32),
+            RecognitionEvent::Command { index: 0, .. }
+        ));
+        assert!(matches!(
+            command_matcher.process("Turn on, the light!", 1f32),
+            RecognitionEvent::Command { index: 0, .. }
+        ));
+        assert!(matches!(
+            command_matcher.process("Don't stop - ", 1f32),
+            RecognitionEvent::Command { index: 1, .. }
+        ));
+        assert_eq!(
+            command_matcher.process("Dont stop.", 1f32),
+            RecognitionEvent::NoMatch {
+                text: "Dont stop.".to_string(),
+                confidence: 1f32,
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_words_do_not_match() {
+        let command_matcher = CommandMatcher::new(commands(), 0f32);
+
+        assert_eq!(
+            command_matcher.process("turn [unk] on", 1f32),
+            RecognitionEvent::NoMatch {
+                text: "turn on".to_string(),
+                confidence: 1f32,
+            }
+        );
+        assert_eq!(
+            command_matcher.process("[unk]", 1f32),
+            RecognitionEvent::NoMatch {
+                text: "".to_string(),
+                confidence: 1f32,
+            }
+        );
+    }
+
+    #[test]
+    fn threshold_is_the_minimum_confidence() {
+        let command_matcher = CommandMatcher::new(commands(), 0.6f32);
+
+        assert!(matches!(
+            command_matcher.process("turn on", 0.6f32),
+            RecognitionEvent::Command { index: 0, .. }
+        ));
+        assert_eq!(
+            command_matcher.process("turn on", 0.59f32),
+            RecognitionEvent::NoMatch {
+                text: "turn on".to_string(),
+                confidence: 0.59f32,
+            }
+        );
+    }
+
+    #[test]
+    fn set_commands_replaces_the_commands() {
+        let mut command_matcher = CommandMatcher::new(commands(), 0f32);
+        command_matcher.set_commands(vec!["Stop".to_string()]);
+
+        assert_eq!(command_matcher.commands(), &["Stop".to_string()]);
+        assert!(matches!(
+            command_matcher.process("turn on", 1f32),
+            RecognitionEvent::NoMatch { .. }
+        ));
+        assert_eq!(
+            command_matcher.process("stop", 1f32),
+            RecognitionEvent::Command {
+                index: 0,
+                phrase: "Stop".to_string(),
+                confidence: 1f32,
+            }
+        );
+    }
+}
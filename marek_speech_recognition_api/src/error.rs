@@ -2,12 +2,25 @@ use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
 
+use crate::RecognizerState;
+
 #[non_exhaustive]
 #[derive(Debug, Clone, PartialEq)]
 pub enum SpeechError {
     LoadLibraryError(String),
     NoLanguageFound(String),
     LanguageFolderError(PathBuf),
+    RecognitionModeNotSupported(String),
+    AudioFormatError(String),
+    DecodingFailed(String),
+
+    /// The method cannot be called in the current state of the recognizer,
+    /// e.g. `write` before `start`.
+    InvalidState(RecognizerState),
+    WorkerDied,
+
+    /// The audio doesn't fit into the queue of the recognizer.
+    QueueFull,
     Unknown,
 }
 
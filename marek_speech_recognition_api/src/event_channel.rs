@@ -0,0 +1,685 @@
+use std::collections::VecDeque;
+use std::mem::discriminant;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures::Stream;
+
+use crate::{EventFilter, RecognitionEvent};
+
+/// Capacity of the channel the recognizer sends the events to.
+/// Each subscriber (see `EventSender::subscribe`) has its own queue and options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EventChannelOptions {
+    /// Maximum number of the events waiting for the consumer. `None` means unbounded.
+    pub capacity: Option<usize>,
+
+    /// What happens when the channel is full.
+    pub overflow_policy: EventOverflowPolicy,
+}
+
+/// What happens when the event channel is full.
+///
+/// Only the partial results and `RecognitionEvent::AudioLevel` can be dropped,
+/// as they are outdated by the next ones. Other events (final results, `Start`, `Stop` etc.)
+/// are always delivered, even if they exceed the capacity. When no queued event
+/// can be dropped, the new partial result or audio level is dropped instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EventOverflowPolicy {
+    /// The new partial result replaces the queued one of the same utterance.
+    #[default]
+    CoalescePartials,
+
+    /// The oldest queued partial result is dropped.
+    DropOldestPartials,
+
+    /// Sending waits until the consumer receives the events. It blocks the backend's thread
+    /// (or the thread calling `Recognizer` methods), so the consumer needs to run elsewhere.
+    /// `Start`, `Stop` and `Error`, which are sent also from the async `Recognizer` methods,
+    /// don't wait and are queued even if they exceed the capacity.
+    /// The receivers with other policies get the event before the waiting starts,
+    /// but the following events are delayed for all the receivers.
+    Block,
+}
+
+/// Creates the channel for the events of the recognizer. More receivers
+/// can be added with `EventSender::subscribe`.
+pub fn event_channel(options: EventChannelOptions) -> (EventSender, EventReceiver) {
+    let hub = Arc::new(Hub {
+        state: Mutex::new(HubState {
+            subscribers: Vec::new(),
+            current_partial: None,
+            sender_count: 1,
+        }),
+    });
+    let receiver = hub.subscribe(EventFilter::all(), options);
+
+    (EventSender { hub }, receiver)
+}
+
+/// Distributes the events to the subscribers.
+struct Hub {
+    state: Mutex<HubState>,
+}
+
+struct HubState {
+    subscribers: Vec<Arc<Subscriber>>,
+
+    /// The last partial result of the current utterance, sent to the late subscribers.
+    current_partial: Option<RecognitionEvent>,
+    sender_count: usize,
+}
+
+impl Hub {
+    fn subscribe(&self, filter: EventFilter, options: EventChannelOptions) -> EventReceiver {
+        let mut state = self.state.lock().unwrap();
+
+        let mut events = VecDeque::new();
+        if let Some(partial) = &state.current_partial {
+            if filter.matches(partial) {
+                events.push_back(partial.clone());
+            }
+        }
+
+        let subscriber = Arc::new(Subscriber {
+            state: Mutex::new(SubscriberState {
+                events,
+                receiver_waker: None,
+                is_closed: state.sender_count == 0,
+                is_receiver_dropped: false,
+                dropped_events: 0,
+            }),
+            space_available: Condvar::new(),
+            options,
+            filter,
+        });
+        state.subscribers.push(subscriber.clone());
+
+        EventReceiver { subscriber }
+    }
+}
+
+/// The queue of one receiver.
+struct Subscriber {
+    state: Mutex<SubscriberState>,
+
+    /// Notified when the receiver takes the events (for `EventOverflowPolicy::Block`).
+    space_available: Condvar,
+    options: EventChannelOptions,
+    filter: EventFilter,
+}
+
+struct SubscriberState {
+    events: VecDeque<RecognitionEvent>,
+    receiver_waker: Option<Waker>,
+
+    /// Set when all the senders are dropped.
+    is_closed: bool,
+    is_receiver_dropped: bool,
+    dropped_events: u64,
+}
+
+impl Subscriber {
+    /// Queues the event according to the overflow policy.
+    /// Returns `false` when the receiver is dropped.
+    fn push(&self, event: RecognitionEvent) -> bool {
+        let options = &self.options;
+        let mut state = self.state.lock().unwrap();
+
+        if options.overflow_policy == EventOverflowPolicy::Block {
+            // waiting in an async method would deadlock a single threaded executor
+            // running also the consumer
+            while state.is_full(options.capacity)
+                && !state.is_receiver_dropped
+                && !is_control(&event)
+            {
+                state = self.space_available.wait(state).unwrap();
+            }
+        } else if state.is_full(options.capacity)
+            && !state.drop_outdated(&event, options.overflow_policy)
+        {
+            // nothing queued can be dropped, so the new partial is dropped instead
+            if is_droppable(&event) {
+                state.dropped_events += 1;
+                return !state.is_receiver_dropped;
+            }
+        }
+
+        if state.is_receiver_dropped {
+            return false;
+        }
+
+        state.events.push_back(event);
+        let waker = state.receiver_waker.take();
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        true
+    }
+
+    fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.is_closed = true;
+        if let Some(waker) = state.receiver_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn is_receiver_dropped(&self) -> bool {
+        self.state.lock().unwrap().is_receiver_dropped
+    }
+}
+
+impl SubscriberState {
+    fn is_full(&self, capacity: Option<usize>) -> bool {
+        capacity.is_some_and(|capacity| self.events.len() >= capacity)
+    }
+
+    /// Makes room for the event according to the policy.
+    /// Returns `false` when no queued event can be dropped.
+    fn drop_outdated(&mut self, event: &RecognitionEvent, policy: EventOverflowPolicy) -> bool {
+        let index = match policy {
+            EventOverflowPolicy::CoalescePartials if is_droppable(event) => {
+                // the events of the same kind queued after the last non droppable event
+                // are outdated by the new one
+                self.events
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .take_while(|(_, queued)| is_droppable(queued))
+                    .find(|(_, queued)| discriminant(*queued) == discriminant(event))
+                    .map(|(index, _)| index)
+                    .or_else(|| self.events.iter().position(is_droppable))
+            }
+            _ => self.events.iter().position(is_droppable),
+        };
+
+        match index {
+            Some(index) => {
+                self.events.remove(index);
+                self.dropped_events += 1;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn is_droppable(event: &RecognitionEvent) -> bool {
+    matches!(
+        event,
+        RecognitionEvent::Recognition {
+            is_final: false,
+            ..
+        } | RecognitionEvent::AudioLevel { .. }
+    )
+}
+
+/// The events sent also from the async `Recognizer` methods (`start`, `abort` etc.).
+fn is_control(event: &RecognitionEvent) -> bool {
+    matches!(
+        event,
+        RecognitionEvent::Start | RecognitionEvent::Stop | RecognitionEvent::Error(_)
+    )
+}
+
+/// The sending side of the event channel, used by the backends.
+pub struct EventSender {
+    hub: Arc<Hub>,
+}
+
+impl EventSender {
+    /// Sends the event to all the subscribers according to the overflow policy.
+    /// Returns `false` when all the receivers are dropped.
+    pub fn send(&self, event: RecognitionEvent) -> bool {
+        // the subscribers are notified without the lock, so a blocked subscriber
+        // doesn't block adding new ones
+        let subscribers = {
+            let mut state = self.hub.state.lock().unwrap();
+            match &event {
+                RecognitionEvent::Recognition {
+                    is_final: false, ..
+                } => state.current_partial = Some(event.clone()),
+                RecognitionEvent::Recognition { .. }
+                | RecognitionEvent::Start
+                | RecognitionEvent::Stop => state.current_partial = None,
+                _ => {}
+            }
+
+            state
+                .subscribers
+                .retain(|subscriber| !subscriber.is_receiver_dropped());
+            let mut subscribers = state.subscribers.clone();
+
+            // the subscribers that may wait are the last ones,
+            // so the others receive the event without delay
+            subscribers.sort_by_key(|subscriber| {
+                subscriber.options.overflow_policy == EventOverflowPolicy::Block
+            });
+            subscribers
+        };
+
+        let mut is_received = false;
+        for subscriber in subscribers {
+            if subscriber.filter.matches(&event) {
+                is_received |= subscriber.push(event.clone());
+            } else {
+                is_received = true;
+            }
+        }
+        is_received
+    }
+
+    /// Adds a receiver of the events passing the filter, with its own capacity
+    /// and overflow policy. If there is a partial result of the current utterance,
+    /// it is received first.
+    pub fn subscribe(&self, filter: EventFilter, options: EventChannelOptions) -> EventReceiver {
+        self.hub.subscribe(filter, options)
+    }
+}
+
+impl Clone for EventSender {
+    fn clone(&self) -> Self {
+        self.hub.state.lock().unwrap().sender_count += 1;
+        Self {
+            hub: self.hub.clone(),
+        }
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        let mut state = self.hub.state.lock().unwrap();
+        state.sender_count -= 1;
+        if state.sender_count == 0 {
+            // the streams end
+            for subscriber in &state.subscribers {
+                subscriber.close();
+            }
+        }
+    }
+}
+
+/// The stream of the events of the recognizer.
+/// It ends when the recognizer (and its threads) are dropped.
+pub struct EventReceiver {
+    subscriber: Arc<Subscriber>,
+}
+
+impl EventReceiver {
+    /// Returns the number of the events dropped because the queue of this receiver was full.
+    pub fn dropped_events(&self) -> u64 {
+        self.subscriber.state.lock().unwrap().dropped_events
+    }
+}
+
+impl Stream for EventReceiver {
+    type Item = RecognitionEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = self.subscriber.state.lock().unwrap();
+
+        if let Some(event) = state.events.pop_front() {
+            self.subscriber.space_available.notify_all();
+            Poll::Ready(Some(event))
+        } else if state.is_closed {
+            Poll::Ready(None)
+        } else {
+            state.receiver_waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        let mut state = self.subscriber.state.lock().unwrap();
+        state.is_receiver_dropped = true;
+        state.events.clear();
+        self.subscriber.space_available.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use futures::task::{noop_waker_ref, waker, ArcWake};
+
+    use super::*;
+    use crate::{EndpointReason, EventKind, SpeechError};
+
+    fn recognition(text: &str, is_final: bool) -> RecognitionEvent {
+        RecognitionEvent::Recognition {
+            text: text.to_string(),
+            is_final,
+            audio_start_time_usec: None,
+            audio_end_time_usec: None,
+            words: None,
+            endpoint_reason: is_final.then_some(EndpointReason::EndOfSpeech),
+            alternatives: Vec::new(),
+            speaker_vector: None,
+        }
+    }
+
+    fn audio_level(rms: f32) -> RecognitionEvent {
+        RecognitionEvent::AudioLevel {
+            rms,
+            level: rms,
+            speech_likelihood: None,
+            audio_time_usec: None,
+        }
+    }
+
+    fn bounded(capacity: usize, overflow_policy: EventOverflowPolicy) -> EventChannelOptions {
+        EventChannelOptions {
+            capacity: Some(capacity),
+            overflow_policy,
+        }
+    }
+
+    fn poll(receiver: &mut EventReceiver) -> Poll<Option<RecognitionEvent>> {
+        Pin::new(receiver).poll_next(&mut Context::from_waker(noop_waker_ref()))
+    }
+
+    /// Returns the queued events without waiting.
+    fn received(receiver: &mut EventReceiver) -> Vec<RecognitionEvent> {
+        let mut events = Vec::new();
+        while let Poll::Ready(Some(event)) = poll(receiver) {
+            events.push(event);
+        }
+        events
+    }
+
+    #[derive(Default)]
+    struct WakeCounter(AtomicUsize);
+
+    impl ArcWake for WakeCounter {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn unbounded_by_default() {
+        let (sender, mut receiver) = event_channel(EventChannelOptions::default());
+
+        for index in 0..100 {
+            assert!(sender.send(recognition(&index.to_string(), false)));
+        }
+
+        assert_eq!(received(&mut receiver).len(), 100);
+        assert_eq!(receiver.dropped_events(), 0);
+    }
+
+    #[test]
+    fn coalesce_partials_replaces_the_same_kind() {
+        let (sender, mut receiver) =
+            event_channel(bounded(3, EventOverflowPolicy::CoalescePartials));
+
+        sender.send(recognition("a", false));
+        sender.send(audio_level(0.1f32));
+        sender.send(recognition("a b", false));
+        sender.send(recognition("a b c", false));
+        sender.send(audio_level(0.2f32));
+
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                recognition("a", false),
+                recognition("a b c", false),
+                audio_level(0.2f32),
+            ]
+        );
+        assert_eq!(receiver.dropped_events(), 2);
+    }
+
+    #[test]
+    fn drop_oldest_partials_drops_the_first_droppable() {
+        let (sender, mut receiver) =
+            event_channel(bounded(3, EventOverflowPolicy::DropOldestPartials));
+
+        sender.send(RecognitionEvent::Start);
+        sender.send(recognition("a", false));
+        sender.send(audio_level(0.1f32));
+        sender.send(recognition("a b", false));
+        sender.send(recognition("a b c", false));
+
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                RecognitionEvent::Start,
+                recognition("a b", false),
+                recognition("a b c", false),
+            ]
+        );
+        assert_eq!(receiver.dropped_events(), 2);
+    }
+
+    #[test]
+    fn finals_start_and_stop_are_never_dropped() {
+        for overflow_policy in [
+            EventOverflowPolicy::CoalescePartials,
+            EventOverflowPolicy::DropOldestPartials,
+        ] {
+            let (sender, mut receiver) = event_channel(bounded(1, overflow_policy));
+
+            sender.send(RecognitionEvent::Start);
+            sender.send(recognition("a", false));
+            sender.send(recognition("a b", true));
+            sender.send(RecognitionEvent::Stop);
+
+            assert_eq!(
+                received(&mut receiver),
+                vec![
+                    RecognitionEvent::Start,
+                    recognition("a b", true),
+                    RecognitionEvent::Stop,
+                ]
+            );
+            assert_eq!(receiver.dropped_events(), 1);
+        }
+    }
+
+    #[test]
+    fn partial_is_dropped_when_only_finals_are_queued() {
+        for overflow_policy in [
+            EventOverflowPolicy::CoalescePartials,
+            EventOverflowPolicy::DropOldestPartials,
+        ] {
+            let (sender, mut receiver) = event_channel(bounded(2, overflow_policy));
+
+            sender.send(RecognitionEvent::Start);
+            sender.send(recognition("a", true));
+            sender.send(recognition("a b", false));
+            sender.send(recognition("a b c", false));
+
+            assert_eq!(
+                received(&mut receiver),
+                vec![RecognitionEvent::Start, recognition("a", true)]
+            );
+            assert_eq!(receiver.dropped_events(), 2);
+
+            // the finals are still queued over the capacity
+            sender.send(recognition("b", true));
+            sender.send(recognition("c", true));
+            sender.send(RecognitionEvent::Stop);
+            assert_eq!(
+                received(&mut receiver),
+                vec![
+                    recognition("b", true),
+                    recognition("c", true),
+                    RecognitionEvent::Stop
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn block_waits_for_the_receiver() {
+        let (sender, mut receiver) = event_channel(bounded(1, EventOverflowPolicy::Block));
+
+        sender.send(recognition("a", false));
+        let sending_thread = thread::spawn(move || {
+            sender.send(recognition("a b", false));
+            sender.send(recognition("a b", true));
+        });
+
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(received(&mut receiver), vec![recognition("a", false)]);
+
+        let mut events = Vec::new();
+        while events.len() < 2 {
+            events.extend(received(&mut receiver));
+            thread::yield_now();
+        }
+        sending_thread.join().unwrap();
+
+        assert_eq!(
+            events,
+            vec![recognition("a b", false), recognition("a b", true)]
+        );
+        assert_eq!(receiver.dropped_events(), 0);
+    }
+
+    #[test]
+    fn block_does_not_wait_with_control_events() {
+        let (sender, mut receiver) = event_channel(bounded(1, EventOverflowPolicy::Block));
+
+        sender.send(RecognitionEvent::Start);
+        sender.send(RecognitionEvent::Error(SpeechError::WorkerDied));
+        sender.send(RecognitionEvent::Stop);
+
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                RecognitionEvent::Start,
+                RecognitionEvent::Error(SpeechError::WorkerDied),
+                RecognitionEvent::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn stream_ends_when_the_senders_are_dropped() {
+        let (sender, mut receiver) = event_channel(EventChannelOptions::default());
+        let sender2 = sender.clone();
+
+        sender.send(RecognitionEvent::Start);
+        drop(sender);
+        assert_eq!(
+            poll(&mut receiver),
+            Poll::Ready(Some(RecognitionEvent::Start))
+        );
+        assert_eq!(poll(&mut receiver), Poll::Pending);
+
+        sender2.send(RecognitionEvent::Stop);
+        drop(sender2);
+        assert_eq!(
+            poll(&mut receiver),
+            Poll::Ready(Some(RecognitionEvent::Stop))
+        );
+        assert_eq!(poll(&mut receiver), Poll::Ready(None));
+    }
+
+    #[test]
+    fn send_fails_when_the_receivers_are_dropped() {
+        let (sender, receiver) = event_channel(EventChannelOptions::default());
+        let subscriber = sender.subscribe(EventFilter::all(), EventChannelOptions::default());
+
+        drop(receiver);
+        assert!(sender.send(RecognitionEvent::Start));
+
+        drop(subscriber);
+        assert!(!sender.send(RecognitionEvent::Start));
+    }
+
+    #[test]
+    fn late_subscriber_receives_the_current_partial() {
+        let (sender, mut receiver) = event_channel(EventChannelOptions::default());
+
+        sender.send(RecognitionEvent::Start);
+        sender.send(recognition("a", false));
+        sender.send(recognition("a b", false));
+        let mut late_receiver =
+            sender.subscribe(EventFilter::all(), EventChannelOptions::default());
+        let mut final_receiver = sender.subscribe(
+            EventFilter::only(&[EventKind::FinalRecognition]),
+            EventChannelOptions::default(),
+        );
+        sender.send(recognition("a b", true));
+
+        // no partial of the next utterance yet
+        let mut next_receiver =
+            sender.subscribe(EventFilter::all(), EventChannelOptions::default());
+        sender.send(RecognitionEvent::Stop);
+
+        assert_eq!(received(&mut receiver).len(), 5);
+        assert_eq!(
+            received(&mut late_receiver),
+            vec![
+                recognition("a b", false),
+                recognition("a b", true),
+                RecognitionEvent::Stop,
+            ]
+        );
+        assert_eq!(
+            received(&mut final_receiver),
+            vec![recognition("a b", true)]
+        );
+        assert_eq!(received(&mut next_receiver), vec![RecognitionEvent::Stop]);
+    }
+
+    #[test]
+    fn subscribers_have_their_own_options() {
+        let (sender, mut receiver) = event_channel(bounded(1, EventOverflowPolicy::Block));
+        let mut subscriber = sender.subscribe(
+            EventFilter::all(),
+            bounded(1, EventOverflowPolicy::CoalescePartials),
+        );
+        let mut unbounded_subscriber =
+            sender.subscribe(EventFilter::all(), EventChannelOptions::default());
+
+        sender.send(recognition("a", false));
+        let sending_thread = thread::spawn(move || {
+            // waits only for the receiver with the `Block` policy
+            sender.send(recognition("a b", false));
+        });
+
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(received(&mut subscriber), vec![recognition("a b", false)]);
+        assert_eq!(subscriber.dropped_events(), 1);
+
+        assert_eq!(received(&mut receiver), vec![recognition("a", false)]);
+        sending_thread.join().unwrap();
+        assert_eq!(received(&mut receiver), vec![recognition("a b", false)]);
+        assert_eq!(received(&mut unbounded_subscriber).len(), 2);
+    }
+
+    #[test]
+    fn receiver_is_woken_up() {
+        let (sender, mut receiver) = event_channel(EventChannelOptions::default());
+        let wake_counter = Arc::new(WakeCounter::default());
+        let waker = waker(wake_counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(Pin::new(&mut receiver).poll_next(&mut cx).is_pending());
+        sender.send(RecognitionEvent::Start);
+        assert_eq!(wake_counter.0.load(Ordering::SeqCst), 1);
+
+        // woken up only once for the registered waker
+        sender.send(RecognitionEvent::Stop);
+        assert_eq!(wake_counter.0.load(Ordering::SeqCst), 1);
+        assert_eq!(received(&mut receiver).len(), 2);
+
+        assert!(Pin::new(&mut receiver).poll_next(&mut cx).is_pending());
+        drop(sender);
+        assert_eq!(wake_counter.0.load(Ordering::SeqCst), 2);
+        assert_eq!(poll(&mut receiver), Poll::Ready(None));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::RecognitionEvent;
+
+/// Kind of `RecognitionEvent`, used to filter the events of a subscriber.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    Start,
+    Stop,
+    StartOfSpeech,
+    EndOfSpeech,
+    EndOfUtterance,
+    EndOfAudio,
+
+    /// `RecognitionEvent::Recognition` with `is_final` not set.
+    PartialRecognition,
+
+    /// `RecognitionEvent::Recognition` with `is_final` set.
+    FinalRecognition,
+    Command,
+    NoMatch,
+    AudioLevel,
+    AudioDropped,
+    Language,
+    Error,
+}
+
+impl EventKind {
+    pub fn of(event: &RecognitionEvent) -> Self {
+        match event {
+            RecognitionEvent::Start => EventKind::Start,
+            RecognitionEvent::Stop => EventKind::Stop,
+            RecognitionEvent::StartOfSpeech { .. } => EventKind::StartOfSpeech,
+            RecognitionEvent::EndOfSpeech { .. } => EventKind::EndOfSpeech,
+            RecognitionEvent::EndOfUtterance { .. } => EventKind::EndOfUtterance,
+            RecognitionEvent::EndOfAudio { .. } => EventKind::EndOfAudio,
+            RecognitionEvent::Recognition {
+                is_final: false, ..
+            } => EventKind::PartialRecognition,
+            RecognitionEvent::Recognition { .. } => EventKind::FinalRecognition,
+            RecognitionEvent::Command { .. } => EventKind::Command,
+            RecognitionEvent::NoMatch { .. } => EventKind::NoMatch,
+            RecognitionEvent::AudioLevel { .. } => EventKind::AudioLevel,
+            RecognitionEvent::AudioDropped { .. } => EventKind::AudioDropped,
+            RecognitionEvent::Language { .. } => EventKind::Language,
+            RecognitionEvent::Error(_) => EventKind::Error,
+        }
+    }
+}
+
+/// Selects the events received by a subscriber (see `Recognizer::subscribe`).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EventFilter {
+    /// `None` means all the events.
+    kinds: Option<Vec<EventKind>>,
+}
+
+impl EventFilter {
+    /// Passes all the events.
+    pub fn all() -> Self {
+        Self { kinds: None }
+    }
+
+    /// Passes only the events of the given kinds.
+    pub fn only(kinds: &[EventKind]) -> Self {
+        Self {
+            kinds: Some(kinds.to_vec()),
+        }
+    }
+
+    pub fn matches(&self, event: &RecognitionEvent) -> bool {
+        match &self.kinds {
+            Some(kinds) => kinds.contains(&EventKind::of(event)),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SpeechError;
+
+    fn recognition(is_final: bool) -> RecognitionEvent {
+        RecognitionEvent::Recognition {
+            text: "hello".to_string(),
+            is_final,
+            audio_start_time_usec: None,
+            audio_end_time_usec: None,
+            words: None,
+            endpoint_reason: None,
+            alternatives: Vec::new(),
+            speaker_vector: None,
+        }
+    }
+
+    #[test]
+    fn kind_of_the_events() {
+        assert_eq!(EventKind::of(&RecognitionEvent::Start), EventKind::Start);
+        assert_eq!(EventKind::of(&RecognitionEvent::Stop), EventKind::Stop);
+        assert_eq!(
+            EventKind::of(&recognition(false)),
+            EventKind::PartialRecognition
+        );
+        assert_eq!(
+            EventKind::of(&recognition(true)),
+            EventKind::FinalRecognition
+        );
+        assert_eq!(
+            EventKind::of(&RecognitionEvent::EndOfSpeech {
+                audio_time_usec: None
+            }),
+            EventKind::EndOfSpeech
+        );
+        assert_eq!(
+            EventKind::of(&RecognitionEvent::Command {
+                index: 0,
+                phrase: "hello".to_string(),
+                confidence: 1f32,
+            }),
+            EventKind::Command
+        );
+        assert_eq!(
+            EventKind::of(&RecognitionEvent::Error(SpeechError::WorkerDied)),
+            EventKind::Error
+        );
+    }
+
+    #[test]
+    fn filter_matches_the_kinds() {
+        let all = EventFilter::all();
+        assert!(all.matches(&RecognitionEvent::Start));
+        assert!(all.matches(&recognition(false)));
+        assert_eq!(EventFilter::default(), all);
+
+        let finals = EventFilter::only(&[EventKind::FinalRecognition, EventKind::Stop]);
+        assert!(finals.matches(&recognition(true)));
+        assert!(finals.matches(&RecognitionEvent::Stop));
+        assert!(!finals.matches(&recognition(false)));
+        assert!(!finals.matches(&RecognitionEvent::Start));
+
+        let nothing = EventFilter::only(&[]);
+        assert!(!nothing.matches(&RecognitionEvent::Start));
+    }
+}
@@ -1,16 +1,36 @@
+mod audio_converter;
+mod audio_format;
+mod audio_level_meter;
+mod command_matcher;
 mod error;
+mod event_channel;
+mod event_filter;
 mod recognition_event;
 mod recognition_mode;
 mod recognizer;
 mod recognizer_factory;
 mod recognizer_info;
 mod recognizer_options;
+mod recognizer_state;
+mod speaker_clustering;
 
+pub use audio_converter::AudioConverter;
+pub use audio_format::{AudioBuffer, AudioFormat, SampleFormat};
+pub use audio_level_meter::AudioLevelMeter;
+pub use command_matcher::CommandMatcher;
 pub use error::{SpeechError, SpeechResult};
+pub use event_channel::{
+    event_channel, EventChannelOptions, EventOverflowPolicy, EventReceiver, EventSender,
+};
+pub use event_filter::{EventFilter, EventKind};
+pub use recognition_event::Alternative;
+pub use recognition_event::EndpointReason;
 pub use recognition_event::RecognitionEvent;
 pub use recognition_event::Word;
 pub use recognition_mode::RecognitionMode;
 pub use recognizer::Recognizer;
-pub use recognizer_factory::RecognizerFactory;
+pub use recognizer_factory::{unique_languages, RecognizerFactory};
 pub use recognizer_info::RecognizerInfo;
 pub use recognizer_options::RecognizerOptions;
+pub use recognizer_state::RecognizerState;
+pub use speaker_clustering::{cosine_similarity, SpeakerClustering};
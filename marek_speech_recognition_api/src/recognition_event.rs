@@ -1,3 +1,5 @@
+use crate::SpeechError;
+
 #[non_exhaustive]
 #[derive(Debug, Clone, PartialEq)]
 pub enum RecognitionEvent {
@@ -15,6 +17,13 @@ pub enum RecognitionEvent {
     /// speech right now.
     EndOfSpeech { audio_time_usec: Option<u64> },
 
+    /// End of utterance has been detected by the end pointer, e.g. the user
+    /// finished the query and is waiting for the answer.
+    EndOfUtterance { audio_time_usec: Option<u64> },
+
+    /// All the written audio has been processed.
+    EndOfAudio { audio_time_usec: Option<u64> },
+
     /// Speech was recognized.
     Recognition {
         text: String,
@@ -24,10 +33,117 @@ pub enum RecognitionEvent {
         audio_end_time_usec: Option<u64>,
 
         words: Option<Vec<Word>>,
+
+        /// Why the result was marked final.
+        /// `None` for partial results or when the recognizer doesn't tell.
+        endpoint_reason: Option<EndpointReason>,
+
+        /// Hypotheses in order of probability, the first one is the same as `text`.
+        /// Empty unless `RecognizerOptions::max_alternatives` is set.
+        alternatives: Vec<Alternative>,
+
+        /// Speaker vector (x-vector) of the utterance, which can be compared
+        /// with the vectors of other utterances by `SpeakerClustering`.
+        /// Provided only in final results by backends configured with a speaker model.
+        speaker_vector: Option<Vec<f32>>,
+    },
+
+    /// The final result matched one of the commands of `RecognitionMode::Commands`.
+    /// Sent after the `Recognition` event of the result.
+    Command {
+        /// Index of the command in `RecognitionMode::Commands`.
+        index: usize,
+
+        /// The command as configured.
+        phrase: String,
+
+        /// Confidence of the result in range 0..1.
+        confidence: f32,
+    },
+
+    /// The final result in `RecognitionMode::Commands` didn't match any command,
+    /// e.g. out-of-grammar speech, or its confidence was below
+    /// `RecognizerOptions::command_confidence_threshold`.
+    NoMatch {
+        /// Recognized text without the unknown word markers (like Vosk's `[unk]`).
+        text: String,
+
+        /// Confidence of the result in range 0..1.
+        confidence: f32,
+    },
+
+    /// Audio level measured at this time, e.g. to display a VU meter.
+    AudioLevel {
+        /// Low-pass filtered RMS in range 0..1.
+        rms: f32,
+
+        /// Loudness in range 0..1 on the logarithmic scale (-60..0 dBFS), like VU meters show it.
+        level: f32,
+
+        /// Likelihood of speech in range 0..1, if the backend reports it (Google does).
+        speech_likelihood: Option<f32>,
+
+        audio_time_usec: Option<u64>,
+    },
+
+    /// Written audio has been dropped, because the queue of the recognizer was full,
+    /// and it is not recognized.
+    AudioDropped {
+        /// Number of the dropped samples.
+        samples: u64,
+
+        /// Time of the audio processed before the dropped audio.
+        audio_time_usec: Option<u64>,
     },
 
     /// Locale detected, e.g. "en-us" or "af-za"
-    Language { id: String },
+    Language {
+        id: String,
+
+        /// Recognizer specific confidence of the detection, if provided.
+        confidence_level: Option<i32>,
+    },
+
+    /// The backend failed while processing audio in the background.
+    /// The same error is also returned by the next `write` or `stop` call.
+    Error(SpeechError),
+}
+
+/// The reason of the final recognition.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointReason {
+    /// Pause in the speech.
+    EndOfSpeech,
+
+    /// End of the utterance, e.g. the end of the query.
+    EndOfUtterance,
+
+    /// No more audio, e.g. the recognizer has been stopped.
+    EndOfAudio,
+
+    /// Hotword has been detected.
+    Hotword,
+
+    /// The recognition has been reset by the user.
+    Reset,
+
+    /// The recognizer failed and produced what it had so far.
+    Error,
+}
+
+/// One of the hypotheses of the recognized speech.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alternative {
+    /// The transcribed text.
+    pub text: String,
+
+    /// Confidence of the hypothesis, if provided by the recognizer.
+    /// The scale is recognizer specific, higher is better.
+    pub confidence: Option<f32>,
+
+    /// Words of the hypothesis, if provided by the recognizer.
+    pub words: Option<Vec<Word>>,
 }
 
 /// A single word and metadata about it.
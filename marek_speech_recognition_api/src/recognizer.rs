@@ -1,16 +1,38 @@
 use async_trait::async_trait;
 
-use crate::{RecognizerInfo, SpeechResult};
+use crate::{
+    AudioBuffer, EventChannelOptions, EventFilter, EventReceiver, RecognizerInfo, RecognizerState,
+    SpeechError, SpeechResult,
+};
 
+/// A speech recognizer created by a `RecognizerFactory`.
+///
+/// `abort`, `pause`, `resume` and `set_commands` have defaults for the backends
+/// without such support. `state` and `subscribe` are required since 3.0.
 #[async_trait]
 pub trait Recognizer {
     /// Returns information about the Recognizer.
     fn info(&self) -> &RecognizerInfo;
 
+    /// Returns the lifecycle state of the recognizer.
+    /// Calls not allowed in the current state return `SpeechError::InvalidState`.
+    fn state(&self) -> RecognizerState;
+
+    /// Returns a new stream of the events passing the filter, in addition to the receiver
+    /// returned with the recognizer, e.g. for a logger or a command dispatcher.
+    /// A subscriber added during an utterance receives its last partial result first.
+    ///
+    /// The stream has its own capacity and overflow policy. A slow subscriber
+    /// with `EventOverflowPolicy::Block` stalls the thread producing the events,
+    /// so all the following events are delayed for every receiver.
+    fn subscribe(&self, filter: EventFilter, options: EventChannelOptions) -> EventReceiver;
+
     /// Starts the recognition.
+    /// Can be called after creation and again after `stop`.
     async fn start(&mut self) -> SpeechResult;
 
     /// Process new chunk of data.
+    /// The samples need to be in the format set in `RecognizerOptions::audio_format`.
     ///
     /// It waits the time needed to process the data,
     /// so the stop method can always be fast.
@@ -18,9 +40,45 @@ pub trait Recognizer {
     /// For 'is_realtime_only' mode the different behaviour is better.
     /// The first call is quick, but the subsequent calls should wait
     /// for the right (real) time.
-    async fn write(&mut self, buffer: &[i16]) -> SpeechResult;
+    async fn write(&mut self, buffer: AudioBuffer<'_>) -> SpeechResult;
 
     /// Stops the recognition.
     /// Finish processing all sent buffers.
     async fn stop(&mut self) -> SpeechResult;
+
+    /// Stops the recognition without processing the remaining audio,
+    /// e.g. when the user cancelled the input. `RecognitionEvent::Stop` is sent
+    /// right away and no final result is reported for the discarded audio.
+    ///
+    /// By default it calls `stop`, so the remaining audio is still processed.
+    async fn abort(&mut self) -> SpeechResult {
+        self.stop().await
+    }
+
+    /// Pauses the recognition. The audio written while paused is ignored,
+    /// but the loaded model and the state of the recognition are kept.
+    async fn pause(&mut self) -> SpeechResult {
+        Err(SpeechError::RecognitionModeNotSupported(
+            "Pausing".to_string(),
+        ))
+    }
+
+    /// Resumes the paused recognition.
+    async fn resume(&mut self) -> SpeechResult {
+        Err(SpeechError::RecognitionModeNotSupported(
+            "Pausing".to_string(),
+        ))
+    }
+
+    /// Replaces the commands of `RecognitionMode::Commands`, also during the recognition.
+    /// The utterance being recognized still uses the old commands,
+    /// the new ones are used from the next utterance.
+    ///
+    /// Supported only if `RecognizerInfo::supports_command_updates` is set.
+    async fn set_commands(&mut self, commands: Vec<String>) -> SpeechResult {
+        let _ = commands;
+        Err(SpeechError::RecognitionModeNotSupported(
+            "Updating commands".to_string(),
+        ))
+    }
 }
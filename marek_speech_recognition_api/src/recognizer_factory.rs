@@ -1,12 +1,41 @@
-use crate::{RecognitionEvent, Recognizer, RecognizerOptions, SpeechResult};
-use futures::channel::mpsc::UnboundedReceiver;
+use crate::{EventReceiver, Recognizer, RecognizerOptions, SpeechError, SpeechResult};
 
 pub trait RecognizerFactory {
     fn create_recognizer(
         &mut self,
         options: RecognizerOptions,
-    ) -> SpeechResult<(
-        Box<dyn Recognizer + Send>,
-        UnboundedReceiver<RecognitionEvent>,
-    )>;
+    ) -> SpeechResult<(Box<dyn Recognizer + Send>, EventReceiver)>;
+
+    /// Returns the languages that can be passed in `RecognizerOptions::language`.
+    ///
+    /// Factories that cannot list their languages return `RecognitionModeNotSupported`.
+    fn available_languages(&self) -> SpeechResult<Vec<String>> {
+        Err(SpeechError::RecognitionModeNotSupported(
+            "Listing languages".to_string(),
+        ))
+    }
+}
+
+/// Returns the languages without duplicates, in the order of their first occurrence.
+/// Helps to implement `RecognizerFactory::available_languages` for the registered models.
+pub fn unique_languages<'a>(languages: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+    let mut unique_languages: Vec<String> = Vec::new();
+    for language in languages {
+        if !unique_languages.contains(language) {
+            unique_languages.push(language.clone());
+        }
+    }
+    unique_languages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_languages_keep_the_order() {
+        let languages = ["en-US", "pl", "en-US", "de", "pl"].map(String::from);
+        assert_eq!(unique_languages(&languages), vec!["en-US", "pl", "de"]);
+        assert!(unique_languages(&[]).is_empty());
+    }
 }
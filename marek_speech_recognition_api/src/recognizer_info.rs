@@ -8,4 +8,7 @@ pub struct RecognizerInfo {
 
     /// Does output has punctuation.
     pub has_punctuation: bool,
+
+    /// Can the commands be replaced during the recognition (see `Recognizer::set_commands`).
+    pub supports_command_updates: bool,
 }
@@ -1,19 +1,47 @@
+use crate::audio_format::AudioFormat;
+use crate::event_channel::EventChannelOptions;
 use crate::recognition_mode::RecognitionMode;
 
 #[non_exhaustive]
 #[derive(Debug, Clone, PartialEq)]
 pub struct RecognizerOptions {
     pub language: String,
-    pub sample_rate: i32,
+
+    /// Other languages that may be spoken in the audio. If the recognizer supports it,
+    /// it switches between the languages and reports them with `RecognitionEvent::Language`.
+    pub alternative_languages: Vec<String>,
+
+    /// Format of the audio passed to `Recognizer::write`.
+    /// It is converted to the format needed by the backend.
+    pub audio_format: AudioFormat,
+
     pub mode: RecognitionMode,
+
+    /// Maximum number of hypotheses reported in `RecognitionEvent::Recognition::alternatives`.
+    /// 0 disables alternatives.
+    pub max_alternatives: u16,
+
+    /// Minimum confidence (0..1) of the final result to be reported
+    /// as `RecognitionEvent::Command`, otherwise `RecognitionEvent::NoMatch` is sent.
+    /// Backends without the confidence (Google libsoda) report it as 1, so the threshold
+    /// has no effect there.
+    pub command_confidence_threshold: f32,
+
+    /// Capacity and overflow policy of the channel returned with the recognizer.
+    /// By default it is unbounded.
+    pub event_channel: EventChannelOptions,
 }
 
 impl Default for RecognizerOptions {
     fn default() -> Self {
         Self {
             language: "en-US".to_string(),
-            sample_rate: 16000,
+            alternative_languages: Vec::new(),
+            audio_format: AudioFormat::default(),
             mode: RecognitionMode::Speech,
+            max_alternatives: 0,
+            command_confidence_threshold: 0f32,
+            event_channel: EventChannelOptions::default(),
         }
     }
 }
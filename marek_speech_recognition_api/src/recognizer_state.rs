@@ -0,0 +1,136 @@
+use crate::{SpeechError, SpeechResult};
+
+/// Lifecycle of the recognizer.
+///
+/// `Created` → `start` → `Running` → `stop` → `Stopping` → `Stopped` → `start` → `Running` ...
+///
+/// `Running` ↔ `pause`/`resume` ↔ `Paused`, and `abort` goes to `Stopped` from any started state.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecognizerState {
+    /// Created by the factory and not started yet.
+    Created,
+
+    /// Started, accepts audio.
+    Running,
+
+    /// Started, but the written audio is ignored.
+    Paused,
+
+    /// `stop` is processing the remaining audio.
+    /// The recognizer stays in this state if `stop` is cancelled,
+    /// then `stop` or `abort` needs to be called.
+    Stopping,
+
+    /// Stopped, can be started again.
+    Stopped,
+}
+
+impl RecognizerState {
+    /// Returns `SpeechError::InvalidState` unless `start` can be called.
+    pub fn check_start(self) -> SpeechResult {
+        match self {
+            RecognizerState::Created | RecognizerState::Stopped => Ok(()),
+            _ => Err(SpeechError::InvalidState(self)),
+        }
+    }
+
+    /// Returns `SpeechError::InvalidState` unless `write` can be called.
+    pub fn check_write(self) -> SpeechResult {
+        match self {
+            RecognizerState::Running | RecognizerState::Paused => Ok(()),
+            _ => Err(SpeechError::InvalidState(self)),
+        }
+    }
+
+    /// Returns `SpeechError::InvalidState` unless `stop` can be called.
+    pub fn check_stop(self) -> SpeechResult {
+        match self {
+            RecognizerState::Running | RecognizerState::Paused | RecognizerState::Stopping => {
+                Ok(())
+            }
+            _ => Err(SpeechError::InvalidState(self)),
+        }
+    }
+
+    /// Returns `SpeechError::InvalidState` unless `abort` can be called.
+    pub fn check_abort(self) -> SpeechResult {
+        self.check_stop()
+    }
+
+    /// Returns `SpeechError::InvalidState` unless `pause` can be called.
+    pub fn check_pause(self) -> SpeechResult {
+        match self {
+            RecognizerState::Running => Ok(()),
+            _ => Err(SpeechError::InvalidState(self)),
+        }
+    }
+
+    /// Returns `SpeechError::InvalidState` unless `resume` can be called.
+    pub fn check_resume(self) -> SpeechResult {
+        match self {
+            RecognizerState::Paused => Ok(()),
+            _ => Err(SpeechError::InvalidState(self)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_STATES: [RecognizerState; 5] = [
+        RecognizerState::Created,
+        RecognizerState::Running,
+        RecognizerState::Paused,
+        RecognizerState::Stopping,
+        RecognizerState::Stopped,
+    ];
+
+    /// Asserts that the check passes exactly in the allowed states.
+    fn assert_allowed(check: fn(RecognizerState) -> SpeechResult, allowed: &[RecognizerState]) {
+        for state in ALL_STATES {
+            match check(state) {
+                Ok(()) => assert!(allowed.contains(&state), "{:?} is not allowed", state),
+                Err(SpeechError::InvalidState(error_state)) => {
+                    assert!(!allowed.contains(&state), "{:?} is allowed", state);
+                    assert_eq!(error_state, state);
+                }
+                Err(error) => panic!("unexpected error {:?}", error),
+            }
+        }
+    }
+
+    #[test]
+    fn start_after_creation_or_stop() {
+        assert_allowed(
+            RecognizerState::check_start,
+            &[RecognizerState::Created, RecognizerState::Stopped],
+        );
+    }
+
+    #[test]
+    fn write_while_started() {
+        assert_allowed(
+            RecognizerState::check_write,
+            &[RecognizerState::Running, RecognizerState::Paused],
+        );
+    }
+
+    #[test]
+    fn stop_and_abort_while_started_or_stopping() {
+        let allowed = [
+            RecognizerState::Running,
+            RecognizerState::Paused,
+            RecognizerState::Stopping,
+        ];
+        assert_allowed(RecognizerState::check_stop, &allowed);
+        assert_allowed(RecognizerState::check_abort, &allowed);
+    }
+
+    #[test]
+    fn pause_and_resume() {
+        assert_allowed(RecognizerState::check_pause, &[RecognizerState::Running]);
+        assert_allowed(RecognizerState::check_resume, &[RecognizerState::Paused]);
+    }
+}
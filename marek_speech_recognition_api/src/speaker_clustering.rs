@@ -0,0 +1,197 @@
+/// Default minimum cosine similarity of the vectors of the same speaker.
+const DEFAULT_THRESHOLD: f32 = 0.5f32;
+
+/// Assigns speaker vectors (`RecognitionEvent::Recognition::speaker_vector`)
+/// to speakers, e.g. to label the utterances in a call recording as "speaker 1" and "speaker 2".
+///
+/// It is an online clustering. Each speaker is represented by the mean of its vectors
+/// and a vector not similar enough to any of the speakers starts a new one.
+pub struct SpeakerClustering {
+    threshold: f32,
+    max_speakers: Option<usize>,
+
+    /// Sums of the vectors of each speaker and their number.
+    speakers: Vec<(Vec<f32>, usize)>,
+}
+
+impl SpeakerClustering {
+    pub fn new() -> Self {
+        Self {
+            threshold: DEFAULT_THRESHOLD,
+            max_speakers: None,
+            speakers: Vec::new(),
+        }
+    }
+
+    /// Sets the minimum cosine similarity (-1..1) of the vector
+    /// to the speaker's mean to be assigned to that speaker.
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Sets the known number of speakers. When reached, vectors are assigned
+    /// to the most similar speaker regardless of the threshold.
+    pub fn with_max_speakers(mut self, max_speakers: usize) -> Self {
+        self.max_speakers = Some(max_speakers.max(1));
+        self
+    }
+
+    /// Returns the index of the speaker (0 for the first speaker) and updates its mean.
+    pub fn identify(&mut self, vector: &[f32]) -> usize {
+        let most_similar = self
+            .speakers
+            .iter()
+            .enumerate()
+            .map(|(index, (sum, _))| (index, cosine_similarity(sum, vector)))
+            .max_by(|(_, similarity1), (_, similarity2)| similarity1.total_cmp(similarity2));
+
+        let is_full = self
+            .max_speakers
+            .is_some_and(|max_speakers| self.speakers.len() >= max_speakers);
+
+        match most_similar {
+            Some((index, similarity)) if similarity >= self.threshold || is_full => {
+                let (sum, count) = &mut self.speakers[index];
+                for (sum, value) in sum.iter_mut().zip(vector) {
+                    *sum += value;
+                }
+                *count += 1;
+                index
+            }
+            _ => {
+                self.speakers.push((vector.to_vec(), 1));
+                self.speakers.len() - 1
+            }
+        }
+    }
+
+    pub fn speaker_count(&self) -> usize {
+        self.speakers.len()
+    }
+
+    /// Forgets all the speakers.
+    pub fn reset(&mut self) {
+        self.speakers.clear();
+    }
+}
+
+impl Default for SpeakerClustering {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cosine similarity of two speaker vectors, from -1 (opposite) to 1 (the same direction).
+/// Returns 0 if the vectors have different lengths or one of them is zero.
+pub fn cosine_similarity(vector1: &[f32], vector2: &[f32]) -> f32 {
+    if vector1.len() != vector2.len() {
+        return 0f32;
+    }
+
+    let dot = vector1
+        .iter()
+        .zip(vector2)
+        .map(|(value1, value2)| value1 * value2)
+        .sum::<f32>();
+    let norm1 = vector1
+        .iter()
+        .map(|value| value * value)
+        .sum::<f32>()
+        .sqrt();
+    let norm2 = vector2
+        .iter()
+        .map(|value| value * value)
+        .sum::<f32>()
+        .sqrt();
+
+    if norm1 == 0f32 || norm2 == 0f32 {
+        0f32
+    } else {
+        dot / (norm1 * norm2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: f32, expected: f32) {
+        assert!((value - expected).abs() < 1e-6, "{} != {}", value, expected);
+    }
+
+    #[test]
+    fn cosine_similarity_of_vectors() {
+        assert_close(cosine_similarity(&[1f32, 2f32], &[2f32, 4f32]), 1f32);
+        assert_close(cosine_similarity(&[1f32, 0f32], &[0f32, 3f32]), 0f32);
+        assert_close(cosine_similarity(&[1f32, 1f32], &[-1f32, -1f32]), -1f32);
+        assert_close(
+            cosine_similarity(&[1f32, 0f32], &[1f32, 1f32]),
+            std::f32::consts::FRAC_1_SQRT_2,
+        );
+
+        assert_eq!(cosine_similarity(&[1f32, 2f32], &[1f32, 2f32, 3f32]), 0f32);
+        assert_eq!(cosine_similarity(&[0f32, 0f32], &[1f32, 2f32]), 0f32);
+        assert_eq!(cosine_similarity(&[], &[]), 0f32);
+    }
+
+    #[test]
+    fn similar_vectors_are_the_same_speaker() {
+        let mut clustering = SpeakerClustering::new();
+
+        assert_eq!(clustering.identify(&[1f32, 0f32, 0f32]), 0);
+        assert_eq!(clustering.identify(&[0f32, 1f32, 0f32]), 1);
+        assert_eq!(clustering.identify(&[0.9f32, 0.1f32, 0f32]), 0);
+        assert_eq!(clustering.identify(&[0.1f32, 1f32, 0.1f32]), 1);
+        assert_eq!(clustering.speaker_count(), 2);
+
+        clustering.reset();
+        assert_eq!(clustering.speaker_count(), 0);
+        assert_eq!(clustering.identify(&[0f32, 1f32, 0f32]), 0);
+    }
+
+    #[test]
+    fn threshold_decides_about_new_speakers() {
+        // the similarity of these vectors is 1/sqrt(2), about 0.707
+        let vector1 = [1f32, 0f32];
+        let vector2 = [1f32, 1f32];
+
+        let mut clustering = SpeakerClustering::new().with_threshold(0.7f32);
+        assert_eq!(clustering.identify(&vector1), 0);
+        assert_eq!(clustering.identify(&vector2), 0);
+
+        let mut clustering = SpeakerClustering::new().with_threshold(0.71f32);
+        assert_eq!(clustering.identify(&vector1), 0);
+        assert_eq!(clustering.identify(&vector2), 1);
+    }
+
+    #[test]
+    fn speaker_mean_follows_the_vectors() {
+        let mut clustering = SpeakerClustering::new().with_threshold(0.9f32);
+
+        // each vector is similar enough to the mean, but not to the first vector
+        assert_eq!(clustering.identify(&[1f32, 0f32]), 0);
+        assert_eq!(clustering.identify(&[1f32, 0.4f32]), 0);
+        assert_eq!(clustering.identify(&[1f32, 0.7f32]), 0);
+        assert!(cosine_similarity(&[1f32, 0f32], &[1f32, 0.7f32]) < 0.9f32);
+        assert_eq!(clustering.speaker_count(), 1);
+    }
+
+    #[test]
+    fn max_speakers_assigns_to_the_most_similar() {
+        let mut clustering = SpeakerClustering::new().with_max_speakers(2);
+
+        assert_eq!(clustering.identify(&[1f32, 0f32, 0f32]), 0);
+        assert_eq!(clustering.identify(&[0f32, 1f32, 0f32]), 1);
+
+        // not similar to any speaker, but there can be only two
+        assert_eq!(clustering.identify(&[0.1f32, 0.2f32, 1f32]), 1);
+        assert_eq!(clustering.identify(&[0.2f32, 0f32, -1f32]), 0);
+        assert_eq!(clustering.speaker_count(), 2);
+
+        // at least one speaker
+        let mut clustering = SpeakerClustering::new().with_max_speakers(0);
+        assert_eq!(clustering.identify(&[1f32, 0f32]), 0);
+        assert_eq!(clustering.identify(&[-1f32, 0f32]), 0);
+    }
+}
@@ -0,0 +1,315 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use futures::channel::oneshot;
+use marek_speech_recognition_api::{SpeechError, SpeechResult};
+
+use crate::VoskBackpressurePolicy;
+
+/// Bounded queue of samples between `VoskRecognizer::write` and the Vosk thread.
+/// The samples are kept in a `VecDeque` allocated once for the whole capacity
+/// and the reader moves them into its own reused buffer.
+pub(crate) struct AudioQueue {
+    state: Mutex<QueueState>,
+    capacity: usize,
+    policy: VoskBackpressurePolicy,
+}
+
+struct QueueState {
+    samples: VecDeque<i16>,
+
+    /// Number of samples dropped by `VoskBackpressurePolicy::DropOldest`
+    /// since the reader took the samples.
+    dropped_samples: u64,
+
+    /// Notified when the reader takes the samples.
+    space_waiter: Option<oneshot::Sender<()>>,
+
+    /// The first decoding error of the queued samples, not yet returned to the writer.
+    error: Option<SpeechError>,
+
+    /// Set when the reader is gone.
+    is_closed: bool,
+}
+
+/// The reading side of the queue, owned by the Vosk thread.
+/// Closes the queue when dropped, so the blocked writer doesn't wait forever.
+pub(crate) struct AudioQueueReader {
+    queue: Arc<AudioQueue>,
+}
+
+impl AudioQueue {
+    pub fn new(capacity: usize, policy: VoskBackpressurePolicy) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                samples: VecDeque::with_capacity(capacity),
+                dropped_samples: 0,
+                space_waiter: None,
+                error: None,
+                is_closed: false,
+            }),
+            capacity: capacity.max(1),
+            policy,
+        }
+    }
+
+    pub fn reader(self: &Arc<Self>) -> AudioQueueReader {
+        AudioQueueReader {
+            queue: self.clone(),
+        }
+    }
+
+    /// Adds the samples according to the backpressure policy.
+    /// Returns the decoding error reported by the reader without adding the samples
+    /// and `SpeechError::WorkerDied` when the reader is gone.
+    pub async fn push(&self, samples: &[i16]) -> SpeechResult {
+        loop {
+            let receiver = {
+                let mut state = self.state.lock().unwrap();
+                if let Some(error) = state.error.take() {
+                    return Err(error);
+                }
+                if state.is_closed {
+                    return Err(SpeechError::WorkerDied);
+                }
+
+                let free_space = self.capacity.saturating_sub(state.samples.len());
+                // the buffer larger than the queue is accepted when the queue is empty
+                if samples.len() <= free_space || state.samples.is_empty() {
+                    state.samples.extend(samples);
+                    return Ok(());
+                }
+
+                match self.policy {
+                    VoskBackpressurePolicy::Block => {
+                        let (sender, receiver) = oneshot::channel();
+                        state.space_waiter = Some(sender);
+                        receiver
+                    }
+                    VoskBackpressurePolicy::DropOldest => {
+                        let overflow = (state.samples.len() + samples.len())
+                            .saturating_sub(self.capacity)
+                            .min(state.samples.len());
+                        state.samples.drain(..overflow);
+                        state.dropped_samples += overflow as u64;
+                        state.samples.extend(samples);
+                        return Ok(());
+                    }
+                    VoskBackpressurePolicy::Error => return Err(SpeechError::QueueFull),
+                }
+            };
+
+            // the sender is dropped when the queue is closed, which is checked above
+            let _ = receiver.await;
+        }
+    }
+
+    /// Returns the decoding error reported by the reader, only once.
+    pub fn take_error(&self) -> Option<SpeechError> {
+        self.state.lock().unwrap().error.take()
+    }
+
+    /// Drops all the queued samples.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.samples.clear();
+        if let Some(sender) = state.space_waiter.take() {
+            let _ = sender.send(());
+        }
+    }
+}
+
+impl AudioQueueReader {
+    /// Moves all the queued samples into the buffer, replacing its content.
+    /// Returns the number of samples dropped since the previous call,
+    /// they were older than the samples in the buffer.
+    pub fn take_all(&self, buffer: &mut Vec<i16>) -> u64 {
+        let mut state = self.queue.state.lock().unwrap();
+        buffer.clear();
+        buffer.extend(state.samples.drain(..));
+        if let Some(sender) = state.space_waiter.take() {
+            let _ = sender.send(());
+        }
+        std::mem::take(&mut state.dropped_samples)
+    }
+
+    /// Keeps the decoding error for the writer.
+    /// The first error is kept until the writer gets it, the later ones are ignored.
+    pub fn report_error(&self, error: SpeechError) {
+        let mut state = self.queue.state.lock().unwrap();
+        if state.error.is_none() {
+            state.error = Some(error);
+        }
+    }
+}
+
+impl Drop for AudioQueueReader {
+    fn drop(&mut self) {
+        let mut state = self.queue.state.lock().unwrap();
+        state.is_closed = true;
+        state.space_waiter = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    fn queue(capacity: usize, policy: VoskBackpressurePolicy) -> Arc<AudioQueue> {
+        Arc::new(AudioQueue::new(capacity, policy))
+    }
+
+    #[test]
+    fn takes_the_samples_in_order() {
+        let queue = queue(4, VoskBackpressurePolicy::Error);
+        let reader = queue.reader();
+        let mut buffer = vec![9i16];
+
+        block_on(queue.push(&[1, 2])).unwrap();
+        block_on(queue.push(&[3, 4])).unwrap();
+        assert_eq!(reader.take_all(&mut buffer), 0);
+        assert_eq!(buffer, vec![1, 2, 3, 4]);
+
+        assert_eq!(reader.take_all(&mut buffer), 0);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn oversized_buffer_is_accepted_by_empty_queue() {
+        let queue = queue(2, VoskBackpressurePolicy::Error);
+        let reader = queue.reader();
+        let mut buffer = Vec::new();
+
+        block_on(queue.push(&[1, 2, 3])).unwrap();
+        assert_eq!(block_on(queue.push(&[4])), Err(SpeechError::QueueFull));
+
+        reader.take_all(&mut buffer);
+        assert_eq!(buffer, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn error_policy_rejects_the_written_samples() {
+        let queue = queue(3, VoskBackpressurePolicy::Error);
+        let reader = queue.reader();
+        let mut buffer = Vec::new();
+
+        block_on(queue.push(&[1, 2])).unwrap();
+        assert_eq!(block_on(queue.push(&[3, 4])), Err(SpeechError::QueueFull));
+        block_on(queue.push(&[3])).unwrap();
+
+        assert_eq!(reader.take_all(&mut buffer), 0);
+        assert_eq!(buffer, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn drop_oldest_policy_counts_the_dropped_samples() {
+        let queue = queue(3, VoskBackpressurePolicy::DropOldest);
+        let reader = queue.reader();
+        let mut buffer = Vec::new();
+
+        block_on(queue.push(&[1, 2])).unwrap();
+        block_on(queue.push(&[3, 4])).unwrap();
+        block_on(queue.push(&[5])).unwrap();
+        assert_eq!(reader.take_all(&mut buffer), 2);
+        assert_eq!(buffer, vec![3, 4, 5]);
+
+        // only the queued samples can be dropped
+        block_on(queue.push(&[6])).unwrap();
+        block_on(queue.push(&[7, 8, 9, 10])).unwrap();
+        assert_eq!(reader.take_all(&mut buffer), 1);
+        assert_eq!(buffer, vec![7, 8, 9, 10]);
+
+        assert_eq!(reader.take_all(&mut buffer), 0);
+    }
+
+    #[test]
+    fn block_policy_waits_for_the_reader() {
+        let queue = queue(3, VoskBackpressurePolicy::Block);
+        let reader = queue.reader();
+        let mut buffer = Vec::new();
+
+        block_on(async {
+            queue.push(&[1, 2]).await.unwrap();
+
+            let mut push = Box::pin(queue.push(&[3, 4]));
+            assert!(futures::poll!(&mut push).is_pending());
+
+            reader.take_all(&mut buffer);
+            assert_eq!(buffer, vec![1, 2]);
+            push.await.unwrap();
+        });
+
+        reader.take_all(&mut buffer);
+        assert_eq!(buffer, vec![3, 4]);
+    }
+
+    #[test]
+    fn clear_wakes_up_the_blocked_writer() {
+        let queue = queue(2, VoskBackpressurePolicy::Block);
+        let reader = queue.reader();
+        let mut buffer = Vec::new();
+
+        block_on(async {
+            queue.push(&[1, 2]).await.unwrap();
+
+            let mut push = Box::pin(queue.push(&[3]));
+            assert!(futures::poll!(&mut push).is_pending());
+
+            queue.clear();
+            push.await.unwrap();
+        });
+
+        reader.take_all(&mut buffer);
+        assert_eq!(buffer, vec![3]);
+    }
+
+    #[test]
+    fn first_decoding_error_is_returned_once() {
+        let queue = queue(4, VoskBackpressurePolicy::Error);
+        let reader = queue.reader();
+        let mut buffer = Vec::new();
+
+        block_on(queue.push(&[1, 2])).unwrap();
+        reader.take_all(&mut buffer);
+        reader.report_error(SpeechError::DecodingFailed("first".to_string()));
+        reader.report_error(SpeechError::DecodingFailed("second".to_string()));
+
+        assert_eq!(
+            block_on(queue.push(&[3])),
+            Err(SpeechError::DecodingFailed("first".to_string()))
+        );
+        assert_eq!(queue.take_error(), None);
+
+        block_on(queue.push(&[4])).unwrap();
+        reader.take_all(&mut buffer);
+        assert_eq!(buffer, vec![4]);
+
+        reader.report_error(SpeechError::DecodingFailed("third".to_string()));
+        assert_eq!(
+            queue.take_error(),
+            Some(SpeechError::DecodingFailed("third".to_string()))
+        );
+    }
+
+    #[test]
+    fn dropped_reader_closes_the_queue() {
+        let queue = queue(2, VoskBackpressurePolicy::Block);
+        let reader = queue.reader();
+
+        block_on(async {
+            queue.push(&[1, 2]).await.unwrap();
+
+            let mut push = Box::pin(queue.push(&[3]));
+            assert!(futures::poll!(&mut push).is_pending());
+
+            drop(reader);
+            assert_eq!(push.await, Err(SpeechError::WorkerDied));
+        });
+
+        assert_eq!(block_on(queue.push(&[4])), Err(SpeechError::WorkerDied));
+    }
+}
@@ -1,6 +1,13 @@
+mod audio_queue;
+mod vosk_model_cache;
+mod vosk_model_metadata;
 mod vosk_recognizer;
 mod vosk_recognizer_factory;
+mod vosk_write_mode;
 
+pub use vosk_model_cache::VoskModelCache;
+pub use vosk_model_metadata::{VoskModelMetadata, VoskModelSize};
 pub use vosk_recognizer::VoskRecognizer;
 pub use vosk_recognizer_factory::VoskModelInfo;
 pub use vosk_recognizer_factory::VoskRecognizerFactory;
+pub use vosk_write_mode::{VoskBackpressurePolicy, VoskWriteMode};
@@ -0,0 +1,206 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use futures::channel::oneshot;
+use marek_speech_recognition_api::{SpeechError, SpeechResult};
+
+/// Vosk models shared by the recognizers, keyed by the model folder.
+///
+/// Models are loaded when they are needed for the first time. When the estimated
+/// memory of the loaded models exceeds the budget, the models not used by any recognizer
+/// are evicted, least recently used first. It is checked when a model is loaded
+/// and when a recognizer is dropped. Without the budget the models are kept
+/// until `evict_idle` is called.
+pub struct VoskModelCache {
+    state: Mutex<CacheState>,
+    memory_budget: Option<u64>,
+}
+
+struct CacheState {
+    entries: HashMap<PathBuf, CacheEntry>,
+    use_counter: u64,
+}
+
+struct CacheEntry {
+    model: Arc<vosk::Model>,
+
+    /// Size of the model files, which is roughly the memory used by the model.
+    size: u64,
+
+    last_used: u64,
+}
+
+impl VoskModelCache {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                use_counter: 0,
+            }),
+            memory_budget: None,
+        }
+    }
+
+    /// Sets the memory (in bytes) the loaded models should fit in.
+    /// The models used by recognizers are never evicted, so it can be exceeded.
+    pub fn with_memory_budget(mut self, memory_budget: u64) -> Self {
+        self.memory_budget = Some(memory_budget);
+        self
+    }
+
+    /// Returns the model, loading it if needed.
+    pub fn get(&self, folder: &Path) -> SpeechResult<Arc<vosk::Model>> {
+        if let Some(model) = self.get_loaded(folder) {
+            return Ok(model);
+        }
+
+        let loaded_model = load_model(folder)?;
+        Ok(self.insert(folder, loaded_model))
+    }
+
+    /// Returns the model, loading it on a separate thread if needed,
+    /// so the loading doesn't block the async runtime.
+    pub async fn get_async(&self, folder: &Path) -> SpeechResult<Arc<vosk::Model>> {
+        if let Some(model) = self.get_loaded(folder) {
+            return Ok(model);
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let path = folder.to_path_buf();
+        thread::spawn(move || {
+            let _ = sender.send(load_model(&path));
+        });
+
+        let loaded_model = receiver.await.map_err(|_| SpeechError::WorkerDied)??;
+        Ok(self.insert(folder, loaded_model))
+    }
+
+    /// Evicts all the models not used by any recognizer.
+    pub fn evict_idle(&self) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .entries
+            .retain(|_, entry| Arc::strong_count(&entry.model) > 1);
+    }
+
+    /// Returns the estimated memory (in bytes) used by the loaded models.
+    pub fn memory_usage(&self) -> u64 {
+        let state = self.state.lock().unwrap();
+        state.entries.values().map(|entry| entry.size).sum()
+    }
+
+    /// Called by a recognizer before it drops the model. If it is the last recognizer
+    /// using the model, the model becomes idle and the budget is enforced.
+    pub(crate) fn release(&self, model: &Arc<vosk::Model>) {
+        if let Some(memory_budget) = self.memory_budget {
+            let mut state = self.state.lock().unwrap();
+            state.evict_least_recently_used(memory_budget, Some(model));
+        }
+    }
+
+    fn get_loaded(&self, folder: &Path) -> Option<Arc<vosk::Model>> {
+        let mut state = self.state.lock().unwrap();
+        state.use_counter += 1;
+        let use_counter = state.use_counter;
+
+        let entry = state.entries.get_mut(folder)?;
+        entry.last_used = use_counter;
+        Some(entry.model.clone())
+    }
+
+    fn insert(&self, folder: &Path, loaded_model: (vosk::Model, u64)) -> Arc<vosk::Model> {
+        let mut state = self.state.lock().unwrap();
+        state.use_counter += 1;
+        let use_counter = state.use_counter;
+
+        // the model could be loaded by other recognizer in the meantime
+        let (model, size) = loaded_model;
+        let model = state
+            .entries
+            .entry(folder.to_path_buf())
+            .or_insert_with(|| CacheEntry {
+                model: Arc::new(model),
+                size,
+                last_used: use_counter,
+            })
+            .model
+            .clone();
+
+        if let Some(memory_budget) = self.memory_budget {
+            state.evict_least_recently_used(memory_budget, None);
+        }
+
+        model
+    }
+}
+
+impl CacheState {
+    /// `released` is the model whose user is about to drop it.
+    fn evict_least_recently_used(
+        &mut self,
+        memory_budget: u64,
+        released: Option<&Arc<vosk::Model>>,
+    ) {
+        let mut memory_usage = self.entries.values().map(|entry| entry.size).sum::<u64>();
+
+        let mut idle = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| {
+                let users = match released {
+                    Some(released) if Arc::ptr_eq(released, &entry.model) => 1,
+                    _ => 0,
+                };
+                Arc::strong_count(&entry.model) == 1 + users
+            })
+            .map(|(folder, entry)| (entry.last_used, entry.size, folder.clone()))
+            .collect::<Vec<_>>();
+        idle.sort();
+
+        for (_, size, folder) in idle {
+            if memory_usage <= memory_budget {
+                break;
+            }
+            self.entries.remove(&folder);
+            memory_usage -= size;
+        }
+    }
+}
+
+impl Default for VoskModelCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn load_model(folder: &Path) -> SpeechResult<(vosk::Model, u64)> {
+    let model = vosk::Model::new(
+        folder
+            .as_os_str()
+            .to_str()
+            .ok_or_else(|| SpeechError::LanguageFolderError(PathBuf::from(folder)))?,
+    )
+    .ok_or_else(|| SpeechError::LanguageFolderError(PathBuf::from(folder)))?;
+
+    Ok((model, folder_size(folder)))
+}
+
+fn folder_size(folder: &Path) -> u64 {
+    fs::read_dir(folder)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| match entry.metadata() {
+                    Ok(metadata) if metadata.is_dir() => folder_size(&entry.path()),
+                    Ok(metadata) => metadata.len(),
+                    Err(_) => 0,
+                })
+                .sum()
+        })
+        .unwrap_or(0)
+}
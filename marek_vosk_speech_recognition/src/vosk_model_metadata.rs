@@ -0,0 +1,281 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use marek_speech_recognition_api::{SpeechError, SpeechResult};
+
+/// Size variant of the model, as in "vosk-model-small-en-us-0.15" vs "vosk-model-en-us-0.22".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoskModelSize {
+    /// Small models for mobile devices, with a runtime-configurable graph.
+    Small,
+
+    /// Big, more accurate models for servers.
+    Large,
+}
+
+/// Information about the model read from its folder.
+#[derive(Debug, Clone)]
+pub struct VoskModelMetadata {
+    /// Name of the model folder, e.g. "vosk-model-small-en-us-0.15".
+    pub name: String,
+
+    /// Language inferred from the name, e.g. "en-US",
+    /// or just "pl" for the models named without the region.
+    pub language: String,
+
+    pub size: VoskModelSize,
+
+    /// Version from the name, e.g. "0.15".
+    pub version: String,
+
+    /// Sample rate the model was trained for, from `conf/mfcc.conf`.
+    pub sample_rate: Option<u32>,
+
+    /// The first line of the model's `README`.
+    pub description: Option<String>,
+
+    pub folder: PathBuf,
+}
+
+impl VoskModelMetadata {
+    /// Reads the metadata of the model in the folder.
+    /// Returns `None` if the folder is not a speech recognition model
+    /// (e.g. a speaker identification model) or it is not named in the standard way.
+    pub fn read(folder: &Path) -> Option<Self> {
+        if !folder.join("conf").join("model.conf").is_file() {
+            return None;
+        }
+
+        let name = folder.file_name()?.to_str()?.to_string();
+        let (language, size, version) = parse_model_name(&name)?;
+
+        Some(Self {
+            name,
+            language,
+            size,
+            version,
+            sample_rate: read_sample_rate(folder),
+            description: read_description(folder),
+            folder: folder.to_path_buf(),
+        })
+    }
+
+    /// Returns the models found in the subfolders of `models_folder`, sorted by name.
+    pub fn discover(models_folder: &Path) -> SpeechResult<Vec<Self>> {
+        let entries = fs::read_dir(models_folder)
+            .map_err(|_| SpeechError::LanguageFolderError(models_folder.to_path_buf()))?;
+
+        let mut models = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| Self::read(&entry.path()))
+            .collect::<Vec<_>>();
+        models.sort_by(|model1, model2| model1.name.cmp(&model2.name));
+
+        Ok(models)
+    }
+
+    /// Numeric parts of the version, so "0.22" is newer than "0.3".
+    pub(crate) fn version_parts(&self) -> Vec<u64> {
+        self.version
+            .split('.')
+            .map(|part| part.parse().unwrap_or_default())
+            .collect()
+    }
+}
+
+/// Parses names like "vosk-model-small-en-us-0.15", "vosk-model-en-us-0.22-lgraph"
+/// or "vosk-model-small-fr-pguyot-0.3".
+fn parse_model_name(name: &str) -> Option<(String, VoskModelSize, String)> {
+    let mut parts = name.strip_prefix("vosk-model-")?.split('-').peekable();
+
+    let size = if parts.next_if_eq(&"small").is_some() {
+        VoskModelSize::Small
+    } else {
+        VoskModelSize::Large
+    };
+
+    let language = parts.next()?;
+    if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_lowercase()) {
+        return None;
+    }
+
+    // speaker identification models, e.g. "vosk-model-spk-0.4"
+    if language == "spk" {
+        return None;
+    }
+    let language = match parts.next_if(|part| is_region(part)) {
+        Some(region) => format!("{}-{}", language, region.to_ascii_uppercase()),
+        None => language.to_string(),
+    };
+
+    // the version can be preceded by the author or the dataset name
+    let version = parts.find(|part| is_version(part))?;
+
+    Some((language, size, version.to_string()))
+}
+
+fn is_region(part: &str) -> bool {
+    part.len() == 2 && part.chars().all(|c| c.is_ascii_lowercase())
+}
+
+fn is_version(part: &str) -> bool {
+    part.starts_with(|c: char| c.is_ascii_digit())
+        && part.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+fn read_sample_rate(folder: &Path) -> Option<u32> {
+    let conf = fs::read_to_string(folder.join("conf").join("mfcc.conf")).ok()?;
+    conf.lines()
+        .find_map(|line| line.trim().strip_prefix("--sample-frequency="))
+        .and_then(|sample_rate| sample_rate.trim().parse().ok())
+}
+
+fn read_description(folder: &Path) -> Option<String> {
+    let readme = fs::read_to_string(folder.join("README")).ok()?;
+    readme
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty())
+        .map(|line| line.to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Creates an empty folder for the test in the temporary folder.
+    pub(crate) fn create_test_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!(
+            "vosk_model_metadata_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    /// Creates the files of a speech recognition model.
+    pub(crate) fn create_model(models_folder: &Path, name: &str, mfcc_conf: &str) -> PathBuf {
+        let folder = models_folder.join(name);
+        fs::create_dir_all(folder.join("conf")).unwrap();
+        fs::write(folder.join("conf").join("model.conf"), "").unwrap();
+        fs::write(folder.join("conf").join("mfcc.conf"), mfcc_conf).unwrap();
+        folder
+    }
+
+    #[test]
+    fn parses_model_names() {
+        let cases = [
+            (
+                "vosk-model-small-en-us-0.15",
+                Some(("en-US", VoskModelSize::Small, "0.15")),
+            ),
+            (
+                "vosk-model-en-us-0.22-lgraph",
+                Some(("en-US", VoskModelSize::Large, "0.22")),
+            ),
+            (
+                "vosk-model-small-fr-pguyot-0.3",
+                Some(("fr", VoskModelSize::Small, "0.3")),
+            ),
+            (
+                "vosk-model-small-pl-0.22",
+                Some(("pl", VoskModelSize::Small, "0.22")),
+            ),
+            ("vosk-model-spk-0.4", None),
+            ("vosk-model-small-en-us", None),
+            ("model-en-us-0.22", None),
+            ("vosk-model-EN-0.22", None),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(
+                parse_model_name(name),
+                expected.map(|(language, size, version)| (
+                    language.to_string(),
+                    size,
+                    version.to_string()
+                )),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn recognizes_versions() {
+        let cases = [
+            ("0.15", true),
+            ("0.22", true),
+            ("1", true),
+            ("lgraph", false),
+            ("pguyot", false),
+            ("v0.3", false),
+            ("", false),
+        ];
+
+        for (part, expected) in cases {
+            assert_eq!(is_version(part), expected, "{}", part);
+        }
+    }
+
+    #[test]
+    fn reads_sample_rate() {
+        let folder = create_test_folder("sample_rate");
+        let cases = [
+            (
+                "--use-energy=false\n--sample-frequency=16000\n",
+                Some(16000),
+            ),
+            ("  --sample-frequency=8000  \n", Some(8000)),
+            ("--use-energy=false\n", None),
+            ("--sample-frequency=fast\n", None),
+        ];
+
+        for (i, (mfcc_conf, expected)) in cases.into_iter().enumerate() {
+            let model_folder = create_model(&folder, &format!("model-{}", i), mfcc_conf);
+            assert_eq!(read_sample_rate(&model_folder), expected, "{}", mfcc_conf);
+        }
+        assert_eq!(read_sample_rate(&folder.join("missing")), None);
+
+        let _ = fs::remove_dir_all(&folder);
+    }
+
+    #[test]
+    fn discovers_only_speech_recognition_models() {
+        let folder = create_test_folder("discover");
+        create_model(
+            &folder,
+            "vosk-model-small-pl-0.22",
+            "--sample-frequency=16000\n",
+        );
+        create_model(&folder, "vosk-model-en-us-0.22-lgraph", "");
+        fs::write(
+            folder.join("vosk-model-en-us-0.22-lgraph").join("README"),
+            "\nUS English model\nfor servers\n",
+        )
+        .unwrap();
+
+        // the speaker model has no `conf/model.conf`
+        fs::create_dir_all(folder.join("vosk-model-spk-0.4")).unwrap();
+        fs::write(folder.join("vosk-model-spk-0.4").join("README"), "").unwrap();
+
+        let models = VoskModelMetadata::discover(&folder).unwrap();
+        assert_eq!(models.len(), 2);
+
+        assert_eq!(models[0].name, "vosk-model-en-us-0.22-lgraph");
+        assert_eq!(models[0].language, "en-US");
+        assert_eq!(models[0].sample_rate, None);
+        assert_eq!(models[0].description.as_deref(), Some("US English model"));
+
+        assert_eq!(models[1].name, "vosk-model-small-pl-0.22");
+        assert_eq!(models[1].size, VoskModelSize::Small);
+        assert_eq!(models[1].sample_rate, Some(16000));
+        assert_eq!(models[1].version_parts(), vec![0, 22]);
+
+        let _ = fs::remove_dir_all(&folder);
+    }
+}
@@ -1,41 +1,58 @@
 use std::{
-    path::{Path, PathBuf},
+    sync::Arc,
     thread::{self, JoinHandle},
 };
 
 use async_trait::async_trait;
-use futures::channel::{
-    mpsc::{self, UnboundedReceiver, UnboundedSender},
-    oneshot,
-};
+use futures::channel::oneshot;
 use marek_speech_recognition_api::{
-    RecognitionEvent, RecognitionMode, Recognizer, RecognizerInfo, SpeechError, SpeechResult, Word,
+    event_channel, Alternative, AudioBuffer, AudioConverter, AudioFormat, AudioLevelMeter,
+    CommandMatcher, EndpointReason, EventChannelOptions, EventFilter, EventReceiver, EventSender,
+    RecognitionEvent, RecognitionMode, Recognizer, RecognizerInfo, RecognizerOptions,
+    RecognizerState, SpeechError, SpeechResult, Word,
 };
 
+use crate::audio_queue::AudioQueue;
+use crate::{VoskModelCache, VoskWriteMode};
+
+/// The word Vosk reports for the speech out of the grammar.
+const UNKNOWN_WORD: &str = "[unk]";
+
 pub struct VoskRecognizer {
     info: RecognizerInfo,
-    model: vosk::Model,
-    sample_rate: i32,
+    model: Arc<vosk::Model>,
+
+    /// Notified when the recognizer doesn't need the model anymore.
+    model_cache: Arc<VoskModelCache>,
+    speaker_model: Option<Arc<vosk::SpeakerModel>>,
+    converter: AudioConverter,
     recognition_mode: RecognitionMode,
-    sender: UnboundedSender<RecognitionEvent>,
+    max_alternatives: u16,
+    command_confidence_threshold: f32,
+    write_mode: VoskWriteMode,
+    sender: EventSender,
+    state: RecognizerState,
+    audio_queue: Option<Arc<AudioQueue>>,
     vosk_thread_sender: Option<std::sync::mpsc::Sender<VoskRecognizerEvent>>,
     vosk_thread_handle: Option<JoinHandle<()>>,
 }
 
 impl VoskRecognizer {
     pub(crate) fn new(
-        model_path: &Path,
-        sample_rate: i32,
-        recognition_mode: RecognitionMode,
-    ) -> SpeechResult<(Self, UnboundedReceiver<RecognitionEvent>)> {
-        let (sender, receiver) = mpsc::unbounded();
-        let model = vosk::Model::new(
-            model_path
-                .as_os_str()
-                .to_str()
-                .ok_or_else(|| SpeechError::LanguageFolderError(PathBuf::from(model_path)))?,
-        )
-        .ok_or_else(|| SpeechError::LanguageFolderError(PathBuf::from(model_path)))?;
+        model: Arc<vosk::Model>,
+        model_cache: Arc<VoskModelCache>,
+        speaker_model: Option<Arc<vosk::SpeakerModel>>,
+        options: RecognizerOptions,
+        write_mode: VoskWriteMode,
+    ) -> SpeechResult<(Self, EventReceiver)> {
+        let (sender, receiver) = event_channel(options.event_channel);
+
+        // Vosk resamples the audio to the model's rate by itself
+        let audio_format = options.audio_format;
+        let converter = AudioConverter::new(
+            audio_format,
+            AudioFormat::mono_i16(audio_format.sample_rate),
+        )?;
 
         Ok((
             VoskRecognizer {
@@ -43,22 +60,89 @@ impl VoskRecognizer {
                     name: "Vosk".to_string(),
                     is_realtime_only: false,
                     has_punctuation: false,
+                    supports_command_updates: true,
                 },
                 model,
-                sample_rate,
-                recognition_mode,
+                model_cache,
+                speaker_model,
+                converter,
+                recognition_mode: options.mode,
+                max_alternatives: options.max_alternatives,
+                command_confidence_threshold: options.command_confidence_threshold,
+                write_mode,
                 sender,
+                state: RecognizerState::Created,
+                audio_queue: None,
                 vosk_thread_sender: None,
                 vosk_thread_handle: None,
             },
             receiver,
         ))
     }
+
+    /// Reports that the Vosk thread is gone. The thread cannot be used anymore
+    /// and the recognizer needs to be stopped and started again.
+    fn worker_died(&mut self) -> SpeechError {
+        self.vosk_thread_sender = None;
+        let _ = self
+            .sender
+            .send(RecognitionEvent::Error(SpeechError::WorkerDied));
+        SpeechError::WorkerDied
+    }
+
+    /// Sends `Stop` or `Abort` to the Vosk thread and waits for it to finish.
+    fn stop_thread(&mut self, message: VoskRecognizerEvent) -> SpeechResult {
+        self.state = RecognizerState::Stopping;
+
+        let handle = match self.vosk_thread_handle.take() {
+            Some(handle) => handle,
+            None => {
+                self.state = RecognizerState::Stopped;
+                return Ok(());
+            }
+        };
+
+        // the thread may be already dead, it is detected by join below
+        if let Some(sender) = self.vosk_thread_sender.take() {
+            let _ = sender.send(message);
+        }
+
+        let is_joined = handle.join().is_ok();
+        self.state = RecognizerState::Stopped;
+
+        if !is_joined {
+            return Err(self.worker_died());
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for VoskRecognizer {
+    fn drop(&mut self) {
+        // end the recognition like `abort` does, so the thread doesn't outlive the recognizer
+        // and no events are produced for the audio nobody is waiting for
+        if let Some(sender) = self.vosk_thread_sender.take() {
+            let _ = sender.send(VoskRecognizerEvent::Abort);
+        }
+        if let Some(handle) = self.vosk_thread_handle.take() {
+            let _ = handle.join();
+        }
+
+        self.model_cache.release(&self.model);
+    }
 }
 
 enum VoskRecognizerEvent {
-    Write(Vec<i16>, oneshot::Sender<()>),
+    Write(Vec<i16>, oneshot::Sender<SpeechResult>),
+
+    /// The audio has been put into the queue (`VoskWriteMode::Queued`).
+    AudioQueued,
+    SetCommands(Vec<String>),
     Stop,
+
+    /// Stops without processing the remaining audio.
+    Abort,
 }
 
 #[async_trait]
@@ -67,164 +151,659 @@ impl Recognizer for VoskRecognizer {
         &self.info
     }
 
+    fn state(&self) -> RecognizerState {
+        self.state
+    }
+
+    fn subscribe(&self, filter: EventFilter, options: EventChannelOptions) -> EventReceiver {
+        self.sender.subscribe(filter, options)
+    }
+
     async fn start(&mut self) -> SpeechResult {
-        let mut recognizer = match &self.recognition_mode {
-            RecognitionMode::Speech => vosk::Recognizer::new(&self.model, self.sample_rate as f32)
-                .ok_or_else(|| {
-                    SpeechError::LoadLibraryError("Cannot create Vosk recognizer".to_string())
-                })?,
-            RecognitionMode::Commands(commands) => {
-                vosk::Recognizer::new_with_grammar(&self.model, self.sample_rate as f32, commands)
-                    .ok_or_else(|| {
-                    SpeechError::LoadLibraryError("Cannot create Vosk recognizer".to_string())
-                })?
-            }
-            _ => panic!(
-                "Recognition mode {:#?} not implemented",
-                self.recognition_mode
-            ),
-        };
+        self.state.check_start()?;
+
+        let sample_rate = self.converter.output_format().sample_rate as f32;
+        let recognizer = create_vosk_recognizer(
+            &self.model,
+            self.speaker_model.as_deref(),
+            sample_rate,
+            &self.recognition_mode,
+            self.max_alternatives,
+        )?;
 
-        recognizer.set_max_alternatives(0);
-        recognizer.set_words(true); // include metadata about words in final result
-        recognizer.set_partial_words(true); // include metadata about words in partial result
+        self.converter.reset();
 
         let (sender, receiver) = std::sync::mpsc::channel();
         self.vosk_thread_sender = Some(sender);
 
-        let result_sender = self.sender.clone();
+        // the queue of the previous session may be closed, so a new one is used
+        let audio_queue = match self.write_mode {
+            VoskWriteMode::WaitForProcessing => None,
+            VoskWriteMode::Queued {
+                queue_depth_ms,
+                policy,
+            } => Some(Arc::new(AudioQueue::new(
+                (sample_rate as u64 * queue_depth_ms as u64 / 1000u64) as usize,
+                policy,
+            ))),
+        };
+        let audio_queue_reader = audio_queue.as_ref().map(|queue| queue.reader());
+        self.audio_queue = audio_queue;
+
+        let mut worker = VoskWorker {
+            recognizer,
+            model: self.model.clone(),
+            speaker_model: self.speaker_model.clone(),
+            sample_rate,
+            max_alternatives: self.max_alternatives,
+            command_matcher: match &self.recognition_mode {
+                RecognitionMode::Commands(commands) => Some(CommandMatcher::new(
+                    commands.clone(),
+                    self.command_confidence_threshold,
+                )),
+                _ => None,
+            },
+            pending_commands: None,
+            last_recognition_event: None,
+            is_between_utterances: true,
+            samples_processed: 0,
+            time_offset_usec: 0,
+            // Vosk doesn't report the audio level, so it is measured here
+            audio_level_meter: AudioLevelMeter::new(sample_rate as u32),
+            result_sender: self.sender.clone(),
+        };
 
         self.vosk_thread_handle = Some(thread::spawn(move || {
-            let mut last_recognition_event = None;
+            let mut is_aborted = false;
+            let mut queued_samples = Vec::new();
 
-            result_sender
-                .unbounded_send(RecognitionEvent::Start)
-                .unwrap();
+            // sending fails only when the receiver is dropped,
+            // in which case nobody is interested in the events anymore
+            let _ = worker.result_sender.send(RecognitionEvent::Start);
 
-            loop {
-                let event = receiver.recv().unwrap();
+            // the loop ends also when the recognizer is dropped without calling stop
+            while let Ok(event) = receiver.recv() {
                 match event {
                     VoskRecognizerEvent::Write(buffer, sender) => {
-                        match recognizer.accept_waveform(&buffer) {
-                            vosk::DecodingState::Running => partial_result(
-                                &mut recognizer,
-                                &mut last_recognition_event,
-                                &result_sender,
-                            ),
-                            vosk::DecodingState::Finalized => finalized_result(
-                                &mut recognizer,
-                                &mut last_recognition_event,
-                                &result_sender,
-                            ),
-                            vosk::DecodingState::Failed => panic!("VoskRecognizer error!"),
+                        let _ = sender.send(worker.write(&buffer));
+                    }
+                    VoskRecognizerEvent::AudioQueued => {
+                        // the audio of more messages may be already taken
+                        if let Some(reader) = &audio_queue_reader {
+                            let dropped_samples = reader.take_all(&mut queued_samples);
+                            worker.report_dropped_samples(dropped_samples);
+                            if !queued_samples.is_empty() {
+                                if let Err(error) = worker.write(&queued_samples) {
+                                    reader.report_error(error);
+                                }
+                            }
                         }
-                        sender.send(()).unwrap();
+                    }
+                    VoskRecognizerEvent::SetCommands(commands) => {
+                        worker.pending_commands = Some(commands);
                     }
                     VoskRecognizerEvent::Stop => break,
+                    VoskRecognizerEvent::Abort => {
+                        is_aborted = true;
+                        break;
+                    }
                 }
+
+                worker.update_commands();
             }
 
-            finalized_result(&mut recognizer, &mut last_recognition_event, &result_sender);
+            if !is_aborted {
+                if let Some(reader) = &audio_queue_reader {
+                    let dropped_samples = reader.take_all(&mut queued_samples);
+                    worker.report_dropped_samples(dropped_samples);
+                    if !queued_samples.is_empty() {
+                        if let Err(error) = worker.write(&queued_samples) {
+                            reader.report_error(error);
+                        }
+                    }
+                }
 
-            result_sender
-                .unbounded_send(RecognitionEvent::Stop)
-                .unwrap();
+                worker.finish();
+            }
+            let _ = worker.result_sender.send(RecognitionEvent::Stop);
         }));
+        self.state = RecognizerState::Running;
 
         Ok(())
     }
 
-    async fn write(&mut self, buffer: &[i16]) -> SpeechResult {
-        let finish_receiver = if let Some(vosk_thread_sender) = &self.vosk_thread_sender {
-            let (finish_sender, finish_receiver) = oneshot::channel::<()>();
-            let message = VoskRecognizerEvent::Write(Vec::from(buffer), finish_sender);
-            vosk_thread_sender.send(message).unwrap();
-            Some(finish_receiver)
-        } else {
-            None
-        };
+    async fn write(&mut self, buffer: AudioBuffer<'_>) -> SpeechResult {
+        self.state.check_write()?;
+        if self.state == RecognizerState::Paused {
+            return Ok(());
+        }
 
-        if let Some(finish_receiver) = finish_receiver {
-            finish_receiver.await.unwrap();
+        if self.vosk_thread_sender.is_none() {
+            return Err(SpeechError::WorkerDied);
         }
+        let buffer = self.converter.convert_i16(buffer)?;
 
-        Ok(())
+        if let Some(audio_queue) = self.audio_queue.clone() {
+            match audio_queue.push(&buffer).await {
+                Ok(()) => {}
+                Err(SpeechError::WorkerDied) => return Err(self.worker_died()),
+                Err(error) => return Err(error),
+            }
+
+            let is_sent = self
+                .vosk_thread_sender
+                .as_ref()
+                .is_some_and(|sender| sender.send(VoskRecognizerEvent::AudioQueued).is_ok());
+            if !is_sent {
+                return Err(self.worker_died());
+            }
+
+            return Ok(());
+        }
+
+        let (finish_sender, finish_receiver) = oneshot::channel::<SpeechResult>();
+        let message = VoskRecognizerEvent::Write(buffer.into_owned(), finish_sender);
+        let is_sent = self
+            .vosk_thread_sender
+            .as_ref()
+            .is_some_and(|sender| sender.send(message).is_ok());
+        if !is_sent {
+            return Err(self.worker_died());
+        }
+
+        match finish_receiver.await {
+            Ok(result) => result,
+            Err(_) => Err(self.worker_died()),
+        }
     }
 
     async fn stop(&mut self) -> SpeechResult {
-        if let Some(sender) = &self.vosk_thread_sender {
-            sender.send(VoskRecognizerEvent::Stop).unwrap();
+        self.state.check_stop()?;
+        self.stop_thread(VoskRecognizerEvent::Stop)?;
+
+        // the queued audio decoded after the last write may have failed
+        match self
+            .audio_queue
+            .as_ref()
+            .and_then(|queue| queue.take_error())
+        {
+            Some(error) => Err(error),
+            None => Ok(()),
         }
+    }
 
-        if let Some(handle) = self.vosk_thread_handle.take() {
-            handle.join().unwrap();
+    async fn abort(&mut self) -> SpeechResult {
+        self.state.check_abort()?;
+
+        // the queued audio is dropped before the Vosk thread gets to it
+        if let Some(audio_queue) = &self.audio_queue {
+            audio_queue.clear();
+        }
+        self.stop_thread(VoskRecognizerEvent::Abort)
+    }
+
+    async fn pause(&mut self) -> SpeechResult {
+        self.state.check_pause()?;
+
+        // the Vosk thread keeps the recognizer and waits for more audio
+        self.state = RecognizerState::Paused;
+        Ok(())
+    }
+
+    async fn resume(&mut self) -> SpeechResult {
+        self.state.check_resume()?;
+        self.state = RecognizerState::Running;
+        Ok(())
+    }
+
+    async fn set_commands(&mut self, commands: Vec<String>) -> SpeechResult {
+        if !matches!(self.recognition_mode, RecognitionMode::Commands(_)) {
+            return Err(SpeechError::RecognitionModeNotSupported(format!(
+                "{:?}",
+                self.recognition_mode
+            )));
+        }
+        self.recognition_mode = RecognitionMode::Commands(commands.clone());
+
+        if let Some(vosk_thread_sender) = &self.vosk_thread_sender {
+            if vosk_thread_sender
+                .send(VoskRecognizerEvent::SetCommands(commands))
+                .is_err()
+            {
+                return Err(self.worker_died());
+            }
         }
 
         Ok(())
     }
 }
 
+/// State owned by the Vosk thread.
+struct VoskWorker {
+    recognizer: vosk::Recognizer,
+    model: Arc<vosk::Model>,
+    speaker_model: Option<Arc<vosk::SpeakerModel>>,
+    sample_rate: f32,
+    max_alternatives: u16,
+    command_matcher: Option<CommandMatcher>,
+
+    /// The commands waiting for the end of the current utterance.
+    pending_commands: Option<Vec<String>>,
+    last_recognition_event: Option<RecognitionEvent>,
+
+    /// Set when all the audio passed to the recognizer has been finalized or it is silence
+    /// since then, so it can be replaced without losing the audio of an utterance.
+    is_between_utterances: bool,
+
+    samples_processed: u64,
+
+    /// Time of the start of the current Vosk recognizer, whose times start from 0.
+    time_offset_usec: u64,
+    audio_level_meter: AudioLevelMeter,
+    result_sender: EventSender,
+}
+
+impl VoskWorker {
+    fn write(&mut self, buffer: &[i16]) -> SpeechResult {
+        for event in self.audio_level_meter.process(buffer.into()) {
+            let _ = self.result_sender.send(event);
+        }
+
+        self.samples_processed += buffer.len() as u64;
+
+        match self.recognizer.accept_waveform(buffer) {
+            vosk::DecodingState::Running => {
+                // the silence after an endpoint is still between the utterances
+                self.is_between_utterances = partial_result(
+                    &mut self.recognizer,
+                    &mut self.last_recognition_event,
+                    &self.result_sender,
+                    self.time_offset_usec,
+                );
+                Ok(())
+            }
+            vosk::DecodingState::Finalized => {
+                // Vosk finalizes the result when the end pointer detects a pause,
+                // all the audio written so far belongs to the finalized utterance
+                self.is_between_utterances = true;
+                if finalized_result(
+                    &mut self.recognizer,
+                    &mut self.last_recognition_event,
+                    &self.result_sender,
+                    EndpointReason::EndOfSpeech,
+                    self.command_matcher.as_ref(),
+                    self.time_offset_usec,
+                ) {
+                    let _ = self.result_sender.send(RecognitionEvent::EndOfSpeech {
+                        audio_time_usec: Some(self.to_usec(self.samples_processed)),
+                    });
+                }
+                Ok(())
+            }
+            vosk::DecodingState::Failed => {
+                let error =
+                    SpeechError::DecodingFailed("Vosk failed to decode the waveform".to_string());
+                let _ = self
+                    .result_sender
+                    .send(RecognitionEvent::Error(error.clone()));
+                Err(error)
+            }
+        }
+    }
+
+    /// Replaces the grammar with the pending commands.
+    fn update_commands(&mut self) {
+        // the grammar can be replaced only between the utterances, otherwise
+        // the audio buffered by Vosk for the current utterance would be lost
+        if !self.is_between_utterances {
+            return;
+        }
+
+        if let Some(commands) = self.pending_commands.take() {
+            match create_vosk_recognizer(
+                &self.model,
+                self.speaker_model.as_deref(),
+                self.sample_rate,
+                &RecognitionMode::Commands(commands.clone()),
+                self.max_alternatives,
+            ) {
+                Ok(recognizer) => {
+                    // the times of the new recognizer start from 0
+                    self.recognizer = recognizer;
+                    self.time_offset_usec = self.to_usec(self.samples_processed);
+                    if let Some(command_matcher) = &mut self.command_matcher {
+                        command_matcher.set_commands(commands);
+                    }
+                }
+                Err(error) => {
+                    let _ = self.result_sender.send(RecognitionEvent::Error(error));
+                }
+            }
+        }
+    }
+
+    /// Reports the final result of the remaining audio.
+    fn finish(&mut self) {
+        finalized_result(
+            &mut self.recognizer,
+            &mut self.last_recognition_event,
+            &self.result_sender,
+            EndpointReason::EndOfAudio,
+            self.command_matcher.as_ref(),
+            self.time_offset_usec,
+        );
+
+        let _ = self.result_sender.send(RecognitionEvent::EndOfAudio {
+            audio_time_usec: Some(self.to_usec(self.samples_processed)),
+        });
+    }
+
+    /// Sends `RecognitionEvent::AudioDropped` for the audio dropped from the queue.
+    fn report_dropped_samples(&self, dropped_samples: u64) {
+        if dropped_samples > 0 {
+            let _ = self.result_sender.send(RecognitionEvent::AudioDropped {
+                samples: dropped_samples,
+                audio_time_usec: Some(self.to_usec(self.samples_processed)),
+            });
+        }
+    }
+
+    fn to_usec(&self, samples: u64) -> u64 {
+        samples * 1000000u64 / self.sample_rate as u64
+    }
+}
+
+fn create_vosk_recognizer(
+    model: &vosk::Model,
+    speaker_model: Option<&vosk::SpeakerModel>,
+    sample_rate: f32,
+    recognition_mode: &RecognitionMode,
+    max_alternatives: u16,
+) -> SpeechResult<vosk::Recognizer> {
+    let mut recognizer = match recognition_mode {
+        RecognitionMode::Speech => vosk::Recognizer::new(model, sample_rate).ok_or_else(|| {
+            SpeechError::LoadLibraryError("Cannot create Vosk recognizer".to_string())
+        })?,
+        RecognitionMode::Commands(commands) => {
+            // without the unknown word any speech would be recognized as one of the commands
+            let mut grammar = commands.clone();
+            if !grammar.iter().any(|phrase| phrase == UNKNOWN_WORD) {
+                grammar.push(UNKNOWN_WORD.to_string());
+            }
+
+            vosk::Recognizer::new_with_grammar(model, sample_rate, &grammar).ok_or_else(|| {
+                SpeechError::LoadLibraryError("Cannot create Vosk recognizer".to_string())
+            })?
+        }
+        _ => {
+            return Err(SpeechError::RecognitionModeNotSupported(format!(
+                "{:?}",
+                recognition_mode
+            )))
+        }
+    };
+
+    if let Some(speaker_model) = speaker_model {
+        recognizer.set_speaker_model(speaker_model);
+    }
+    recognizer.set_max_alternatives(max_alternatives);
+    recognizer.set_words(true); // include metadata about words in final result
+    recognizer.set_partial_words(true); // include metadata about words in partial result
+
+    Ok(recognizer)
+}
+
+/// Returns `true` if there is no speech since the last endpoint.
 fn partial_result(
     recognizer: &mut vosk::Recognizer,
     last_recognition_event: &mut Option<RecognitionEvent>,
-    result_sender: &UnboundedSender<RecognitionEvent>,
-) {
+    result_sender: &EventSender,
+    time_offset_usec: u64,
+) -> bool {
     let result = recognizer.partial_result();
-    let text = result.partial;
-    let words = result.partial_result;
+    let is_silence = is_silence(result.partial);
+    let text = without_unknown_words(result.partial);
+    let words = to_words(result.partial_result, time_offset_usec);
 
-    send_recognition_event(result_sender, last_recognition_event, text, words, false);
+    send_recognition_event(
+        result_sender,
+        last_recognition_event,
+        &text,
+        words,
+        Vec::new(),
+        None,
+        None,
+    );
+
+    is_silence
 }
 
+/// Tells from the partial result whether Vosk has heard no speech since the last endpoint,
+/// so the recognizer can be replaced without losing an utterance.
+/// The unknown words are speech too, just out of the grammar.
+fn is_silence(partial: &str) -> bool {
+    partial.trim().is_empty()
+}
+
+/// Returns `true` if the final result (or the command matching it) has been sent.
 fn finalized_result(
     recognizer: &mut vosk::Recognizer,
     last_recognition_event: &mut Option<RecognitionEvent>,
-    result_sender: &UnboundedSender<RecognitionEvent>,
-) {
-    let result = recognizer.result().single().unwrap();
-    let text = result.text;
-    let words = result.result;
+    result_sender: &EventSender,
+    endpoint_reason: EndpointReason,
+    command_matcher: Option<&CommandMatcher>,
+    time_offset_usec: u64,
+) -> bool {
+    let (is_sent, command_event) = match recognizer.result() {
+        vosk::CompleteResult::Single(result) => {
+            let command_event = command_event(
+                command_matcher,
+                result.text,
+                words_confidence(&result.result),
+            );
+            let text = without_unknown_words(result.text);
+            let words = to_words(result.result, time_offset_usec);
+            let speaker_vector = result.speaker_info.map(|speaker_info| speaker_info.vector);
+
+            let is_sent = send_recognition_event(
+                result_sender,
+                last_recognition_event,
+                &text,
+                words,
+                Vec::new(),
+                Some(endpoint_reason),
+                speaker_vector,
+            );
+            (is_sent, command_event)
+        }
+        vosk::CompleteResult::Multiple(result) => {
+            // Vosk doesn't provide confidence of words in alternatives
+            let command_event = result.alternatives.first().and_then(|best| {
+                command_event(
+                    command_matcher,
+                    best.text,
+                    best_alternative_confidence(&result.alternatives),
+                )
+            });
+
+            let alternatives = result
+                .alternatives
+                .into_iter()
+                .map(|alternative| to_alternative(alternative, time_offset_usec))
+                .collect::<Vec<_>>();
+
+            let is_sent = if let Some(best) = alternatives.first() {
+                let text = best.text.clone();
+                let words = best.words.clone().unwrap_or_default();
+
+                send_recognition_event(
+                    result_sender,
+                    last_recognition_event,
+                    &text,
+                    words,
+                    alternatives,
+                    Some(endpoint_reason),
+                    // Vosk reports the speaker only in the results without alternatives
+                    None,
+                )
+            } else {
+                false
+            };
+            (is_sent, command_event)
+        }
+    };
+
+    if let Some(command_event) = command_event {
+        let _ = result_sender.send(command_event);
+        true
+    } else {
+        is_sent
+    }
+}
+
+/// Matches the text including the unknown words, which mark out-of-grammar speech.
+fn command_event(
+    command_matcher: Option<&CommandMatcher>,
+    text: &str,
+    confidence: f32,
+) -> Option<RecognitionEvent> {
+    let command_matcher = command_matcher?;
+    if text.is_empty() {
+        return None;
+    }
+
+    Some(command_matcher.process(text, confidence))
+}
+
+/// Mean confidence (0..1) of the words.
+fn words_confidence(words: &[vosk::Word]) -> f32 {
+    if words.is_empty() {
+        1.0f32
+    } else {
+        words.iter().map(|word| word.conf).sum::<f32>() / words.len() as f32
+    }
+}
+
+/// Confidence (0..1) of the first alternative. Vosk's confidences of the alternatives
+/// are log-likelihood scores, so they are normalized with softmax to the posterior
+/// probability of the best one among the alternatives.
+fn best_alternative_confidence(alternatives: &[vosk::Alternative]) -> f32 {
+    let Some(best) = alternatives.first() else {
+        return 0f32;
+    };
 
-    send_recognition_event(result_sender, last_recognition_event, text, words, true);
+    let max_confidence = alternatives
+        .iter()
+        .map(|alternative| alternative.confidence)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let sum = alternatives
+        .iter()
+        .map(|alternative| (alternative.confidence - max_confidence).exp())
+        .sum::<f32>();
+
+    (best.confidence - max_confidence).exp() / sum
+}
+
+fn without_unknown_words(text: &str) -> String {
+    text.split_whitespace()
+        .filter(|word| *word != UNKNOWN_WORD)
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
+/// Returns `true` if the event has been sent. Empty results and repeated events are skipped.
 fn send_recognition_event(
-    result_sender: &UnboundedSender<RecognitionEvent>,
+    result_sender: &EventSender,
     last_recognition_event: &mut Option<RecognitionEvent>,
     text: &str,
-    words: Vec<vosk::Word>,
-    is_final: bool,
-) {
-    if !words.is_empty() && !text.is_empty() {
-        let recognition_event = Some(to_recognition_event(text, words, is_final));
-
-        if recognition_event != *last_recognition_event {
-            if let Some(recognition_event) = recognition_event {
-                result_sender
-                    .unbounded_send(recognition_event.clone())
-                    .unwrap();
-                last_recognition_event.replace(recognition_event);
-            }
-        }
+    words: Vec<Word>,
+    alternatives: Vec<Alternative>,
+    endpoint_reason: Option<EndpointReason>,
+    speaker_vector: Option<Vec<f32>>,
+) -> bool {
+    if words.is_empty() || text.is_empty() {
+        return false;
     }
+
+    let recognition_event =
+        to_recognition_event(text, words, alternatives, endpoint_reason, speaker_vector);
+    if last_recognition_event.as_ref() == Some(&recognition_event) {
+        return false;
+    }
+
+    let _ = result_sender.send(recognition_event.clone());
+    last_recognition_event.replace(recognition_event);
+
+    true
 }
 
-fn to_recognition_event(text: &str, words: Vec<vosk::Word>, is_final: bool) -> RecognitionEvent {
+fn to_recognition_event(
+    text: &str,
+    words: Vec<Word>,
+    alternatives: Vec<Alternative>,
+    endpoint_reason: Option<EndpointReason>,
+    speaker_vector: Option<Vec<f32>>,
+) -> RecognitionEvent {
     RecognitionEvent::Recognition {
         text: text.to_string(),
-        is_final,
-        audio_start_time_usec: Some((words[0].start * 1000000f32) as u64),
-        audio_end_time_usec: Some((words[words.len() - 1].end * 1000000f32) as u64),
+        is_final: endpoint_reason.is_some(),
+        audio_start_time_usec: Some(words[0].start_time_usec),
+        audio_end_time_usec: Some(words[words.len() - 1].end_time_usec),
+        words: Some(words),
+        endpoint_reason,
+        alternatives,
+        speaker_vector,
+    }
+}
+
+/// `time_offset_usec` is added to the times, which Vosk reports from the start of the recognizer.
+fn to_alternative(alternative: vosk::Alternative, time_offset_usec: u64) -> Alternative {
+    Alternative {
+        text: without_unknown_words(alternative.text),
+        confidence: Some(alternative.confidence),
         words: Some(
-            words
+            alternative
+                .result
                 .into_iter()
+                .filter(|word| word.word != UNKNOWN_WORD)
                 .map(|word| Word {
-                    conf: word.conf,
-                    start_time_usec: (word.start * 1000000f32) as u64,
-                    end_time_usec: (word.end * 1000000f32) as u64,
+                    // Vosk doesn't provide confidence of words in alternatives
+                    conf: 1.0f32,
+                    start_time_usec: time_offset_usec + (word.start * 1000000f32) as u64,
+                    end_time_usec: time_offset_usec + (word.end * 1000000f32) as u64,
                     word: word.word.to_string(),
                 })
                 .collect(),
         ),
     }
 }
+
+/// `time_offset_usec` is added to the times, which Vosk reports from the start of the recognizer.
+fn to_words(words: Vec<vosk::Word>, time_offset_usec: u64) -> Vec<Word> {
+    words
+        .into_iter()
+        .filter(|word| word.word != UNKNOWN_WORD)
+        .map(|word| Word {
+            conf: word.conf,
+            start_time_usec: time_offset_usec + (word.start * 1000000f32) as u64,
+            end_time_usec: time_offset_usec + (word.end * 1000000f32) as u64,
+            word: word.word.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_can_be_replaced_during_silence() {
+        // Vosk's partial result after an endpoint, before the next utterance
+        assert!(is_silence(""));
+        assert!(is_silence(" "));
+
+        assert!(!is_silence("turn"));
+        assert!(!is_silence("[unk]"));
+        assert!(!is_silence("turn [unk]"));
+    }
+}
@@ -1,12 +1,19 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use futures::channel::mpsc::UnboundedReceiver;
-use marek_speech_recognition_api::{Recognizer, RecognizerFactory, SpeechError, SpeechResult};
+use marek_speech_recognition_api::{
+    unique_languages, EventReceiver, Recognizer, RecognizerFactory, RecognizerOptions, SpeechError,
+    SpeechResult,
+};
 
-use crate::VoskRecognizer;
+use crate::{VoskModelCache, VoskModelMetadata, VoskModelSize, VoskRecognizer, VoskWriteMode};
 
 pub struct VoskRecognizerFactory {
     models: Vec<VoskModelInfo>,
+    model_cache: Arc<VoskModelCache>,
+    speaker_model_folder: Option<PathBuf>,
+    speaker_model: Option<Arc<vosk::SpeakerModel>>,
+    write_mode: VoskWriteMode,
 }
 
 pub struct VoskModelInfo {
@@ -16,29 +23,224 @@ pub struct VoskModelInfo {
 
 impl VoskRecognizerFactory {
     pub fn new(models: Vec<VoskModelInfo>) -> SpeechResult<Self> {
-        Ok(Self { models })
+        Ok(Self {
+            models,
+            model_cache: Arc::new(VoskModelCache::new()),
+            speaker_model_folder: None,
+            speaker_model: None,
+            write_mode: VoskWriteMode::default(),
+        })
+    }
+
+    /// Uses the model cache, which can be shared with other factories
+    /// or configured with a memory budget.
+    pub fn with_model_cache(mut self, model_cache: Arc<VoskModelCache>) -> Self {
+        self.model_cache = model_cache;
+        self
+    }
+
+    /// Uses the speaker identification model (e.g. "vosk-model-spk-0.4"),
+    /// so the final results contain the speaker vectors.
+    /// It is loaded when the first recognizer is created.
+    ///
+    /// Vosk cannot report the speaker vectors together with the alternatives,
+    /// so `create_recognizer` fails when `RecognizerOptions::max_alternatives` is not 0.
+    pub fn with_speaker_model<T: Into<PathBuf>>(mut self, folder: T) -> Self {
+        self.speaker_model_folder = Some(folder.into());
+        self.speaker_model = None;
+        self
+    }
+
+    /// Sets how the recognizers pass the written audio to the Vosk thread.
+    /// By default `write` waits until the audio is processed.
+    pub fn with_write_mode(mut self, write_mode: VoskWriteMode) -> Self {
+        self.write_mode = write_mode;
+        self
+    }
+
+    pub fn model_cache(&self) -> &Arc<VoskModelCache> {
+        &self.model_cache
+    }
+
+    /// Loads the model of the language on a separate thread,
+    /// so `create_recognizer` doesn't need to wait for it.
+    pub async fn preload(&self, language: &str) -> SpeechResult {
+        let model_path = self.find_model(language)?;
+        self.model_cache.get_async(model_path).await?;
+        Ok(())
+    }
+
+    /// Uses the models found in the subfolders of `models_folder`,
+    /// e.g. "/usr/local/share/vosk-models" with "vosk-model-small-en-us-0.15" inside.
+    ///
+    /// When there are more models for the language, the newest one
+    /// of the preferred size is used (or the newest one of the other size).
+    pub fn from_models_folder<T: Into<PathBuf>>(
+        models_folder: T,
+        preferred_size: VoskModelSize,
+    ) -> SpeechResult<Self> {
+        let mut discovered = VoskModelMetadata::discover(&models_folder.into())?;
+        discovered.sort_by_key(|model| (model.size == preferred_size, model.version_parts()));
+
+        // the best model of each language is the last one
+        let mut models: Vec<VoskModelInfo> = Vec::new();
+        for model in discovered.into_iter().rev() {
+            if !models.iter().any(|el| el.language == model.language) {
+                models.push(VoskModelInfo {
+                    language: model.language,
+                    folder: model.folder,
+                });
+            }
+        }
+        models.sort_by(|model1, model2| model1.language.cmp(&model2.language));
+
+        Self::new(models)
+    }
+
+    fn get_speaker_model(&mut self) -> SpeechResult<Option<Arc<vosk::SpeakerModel>>> {
+        if let (Some(folder), None) = (&self.speaker_model_folder, &self.speaker_model) {
+            let speaker_model = vosk::SpeakerModel::new(
+                folder
+                    .to_str()
+                    .ok_or_else(|| SpeechError::LanguageFolderError(folder.clone()))?,
+            )
+            .ok_or_else(|| SpeechError::LanguageFolderError(folder.clone()))?;
+            self.speaker_model = Some(Arc::new(speaker_model));
+        }
+
+        Ok(self.speaker_model.clone())
+    }
+
+    fn find_model(&self, language: &str) -> SpeechResult<&Path> {
+        self.models
+            .iter()
+            .find(|el| el.language == language)
+            .map(|el| el.folder.as_path())
+            .ok_or_else(|| SpeechError::NoLanguageFound(language.to_string()))
     }
 }
 
 impl RecognizerFactory for VoskRecognizerFactory {
     fn create_recognizer(
         &mut self,
-        options: marek_speech_recognition_api::RecognizerOptions,
-    ) -> marek_speech_recognition_api::SpeechResult<(
-        Box<dyn Recognizer + Send>,
-        UnboundedReceiver<marek_speech_recognition_api::RecognitionEvent>,
-    )> {
-        let model_path = self
-            .models
-            .iter()
-            .filter(|el| el.language == options.language)
-            .map(|el| el.folder.clone())
-            .next()
-            .ok_or(SpeechError::NoLanguageFound(options.language))?;
+        options: RecognizerOptions,
+    ) -> SpeechResult<(Box<dyn Recognizer + Send>, EventReceiver)> {
+        // Vosk reports the speaker vectors only in the results without alternatives,
+        // so the speaker model would be silently ignored
+        if self.speaker_model_folder.is_some() && options.max_alternatives > 0 {
+            return Err(SpeechError::RecognitionModeNotSupported(
+                "Speaker identification with alternatives".to_string(),
+            ));
+        }
+
+        let model = self.model_cache.get(self.find_model(&options.language)?)?;
+
+        let speaker_model = self.get_speaker_model()?;
 
-        let (recognizer, receiver) =
-            VoskRecognizer::new(&model_path, options.sample_rate, options.mode)?;
+        let (recognizer, receiver) = VoskRecognizer::new(
+            model,
+            self.model_cache.clone(),
+            speaker_model,
+            options,
+            self.write_mode,
+        )?;
 
         Ok((Box::new(recognizer), receiver))
     }
+
+    fn available_languages(&self) -> SpeechResult<Vec<String>> {
+        Ok(unique_languages(
+            self.models.iter().map(|model| &model.language),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::vosk_model_metadata::tests::{create_model, create_test_folder};
+
+    #[test]
+    fn from_models_folder_prefers_size_then_newest_version() {
+        let folder = create_test_folder("factory");
+        for name in [
+            "vosk-model-small-en-us-0.15",
+            "vosk-model-small-en-us-0.3",
+            "vosk-model-en-us-0.22-lgraph",
+            "vosk-model-en-us-0.21",
+            "vosk-model-pl-0.22",
+            "vosk-model-small-fr-pguyot-0.3",
+        ] {
+            create_model(&folder, name, "");
+        }
+        fs::create_dir_all(folder.join("vosk-model-spk-0.4")).unwrap();
+
+        let models = |preferred_size| {
+            VoskRecognizerFactory::from_models_folder(&folder, preferred_size)
+                .unwrap()
+                .models
+                .into_iter()
+                .map(|model| {
+                    (
+                        model.language,
+                        model
+                            .folder
+                            .file_name()
+                            .unwrap()
+                            .to_str()
+                            .unwrap()
+                            .to_string(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // "0.15" is newer than "0.3"; other sizes are used when there is no preferred one
+        assert_eq!(
+            models(VoskModelSize::Small),
+            vec![
+                (
+                    "en-US".to_string(),
+                    "vosk-model-small-en-us-0.15".to_string()
+                ),
+                (
+                    "fr".to_string(),
+                    "vosk-model-small-fr-pguyot-0.3".to_string()
+                ),
+                ("pl".to_string(), "vosk-model-pl-0.22".to_string()),
+            ]
+        );
+        assert_eq!(
+            models(VoskModelSize::Large),
+            vec![
+                (
+                    "en-US".to_string(),
+                    "vosk-model-en-us-0.22-lgraph".to_string()
+                ),
+                (
+                    "fr".to_string(),
+                    "vosk-model-small-fr-pguyot-0.3".to_string()
+                ),
+                ("pl".to_string(), "vosk-model-pl-0.22".to_string()),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&folder);
+    }
+
+    #[test]
+    fn speaker_model_cannot_be_used_with_alternatives() {
+        let mut factory = VoskRecognizerFactory::new(Vec::new())
+            .unwrap()
+            .with_speaker_model("vosk-model-spk-0.4");
+        let mut options = RecognizerOptions::default();
+        options.max_alternatives = 3;
+
+        assert!(matches!(
+            factory.create_recognizer(options),
+            Err(SpeechError::RecognitionModeNotSupported(_))
+        ));
+    }
 }
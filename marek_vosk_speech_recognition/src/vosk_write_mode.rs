@@ -0,0 +1,35 @@
+/// How `VoskRecognizer::write` passes the audio to the Vosk thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoskWriteMode {
+    /// `write` returns when the audio has been processed
+    /// and reports the decoding errors.
+    #[default]
+    WaitForProcessing,
+
+    /// `write` puts the audio into a queue processed by the Vosk thread
+    /// and returns right away, so the capture is not slowed down by the decoding.
+    /// The decoding errors are sent as `RecognitionEvent::Error` and the first one
+    /// is returned also by the next `write` or `stop`, without queueing the written audio.
+    Queued {
+        /// Size of the queue in milliseconds of audio.
+        queue_depth_ms: u32,
+
+        /// What `write` does when the queue is full.
+        policy: VoskBackpressurePolicy,
+    },
+}
+
+/// What `VoskRecognizer::write` does when the audio queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoskBackpressurePolicy {
+    /// Waits until the Vosk thread makes room for the audio.
+    Block,
+
+    /// Drops the oldest audio from the queue. The dropped audio is not recognized
+    /// and the times of the following results are shifted.
+    /// The dropped audio is reported with `RecognitionEvent::AudioDropped`.
+    DropOldest,
+
+    /// Returns `SpeechError::QueueFull` and drops the written audio.
+    Error,
+}
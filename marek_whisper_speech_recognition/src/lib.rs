@@ -0,0 +1,7 @@
+mod speech_segmenter;
+mod whisper_recognizer;
+mod whisper_recognizer_factory;
+
+pub use whisper_recognizer::WhisperRecognizer;
+pub use whisper_recognizer_factory::WhisperModelInfo;
+pub use whisper_recognizer_factory::WhisperRecognizerFactory;
@@ -0,0 +1,264 @@
+/// Length of the frame for which the energy is measured (20 ms at 16 kHz).
+const FRAME_SAMPLES: usize = 320;
+
+/// RMS level above which the frame is considered to contain speech (about -40 dBFS).
+const SPEECH_RMS_THRESHOLD: f32 = 0.01f32;
+
+/// Silence kept before the start of speech, so the first word is not cut (300 ms).
+const PREROLL_SAMPLES: usize = 4800;
+
+/// Pause length that ends the utterance (600 ms).
+const END_OF_SPEECH_SAMPLES: usize = 9600;
+
+/// Whisper processes at most 30 seconds of audio at once.
+const MAX_SEGMENT_SAMPLES: usize = 30 * 16000;
+
+pub(crate) enum SegmenterEvent {
+    StartOfSpeech {
+        sample: usize,
+    },
+    EndOfSpeech {
+        sample: usize,
+    },
+    Segment {
+        start_sample: usize,
+        samples: Vec<f32>,
+    },
+}
+
+/// Splits 16 kHz mono audio into utterances using a simple energy detector.
+pub(crate) struct SpeechSegmenter {
+    /// Audio not yet assigned to a frame.
+    pending: Vec<f32>,
+
+    /// Audio of the current segment (or preroll when not in speech).
+    segment: Vec<f32>,
+
+    /// Position of `segment[0]` in the whole stream.
+    segment_start_sample: usize,
+
+    is_speech: bool,
+    silence_samples: usize,
+}
+
+impl SpeechSegmenter {
+    pub fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            segment: Vec::new(),
+            segment_start_sample: 0,
+            is_speech: false,
+            silence_samples: 0,
+        }
+    }
+
+    pub fn push(&mut self, samples: &[f32]) -> Vec<SegmenterEvent> {
+        let mut events = Vec::new();
+
+        self.pending.extend_from_slice(samples);
+        let frames_len = self.pending.len() - self.pending.len() % FRAME_SAMPLES;
+        let frames = self.pending.drain(..frames_len).collect::<Vec<_>>();
+
+        for frame in frames.chunks_exact(FRAME_SAMPLES) {
+            self.push_frame(frame, &mut events);
+        }
+
+        events
+    }
+
+    /// Ends the stream. Returns the unfinished utterance, if any.
+    pub fn flush(&mut self) -> Vec<SegmenterEvent> {
+        let mut events = Vec::new();
+
+        let pending = std::mem::take(&mut self.pending);
+        if self.is_speech {
+            self.segment.extend_from_slice(&pending);
+            self.end_segment(&mut events);
+        }
+
+        *self = Self::new();
+
+        events
+    }
+
+    fn push_frame(&mut self, frame: &[f32], events: &mut Vec<SegmenterEvent>) {
+        let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt();
+        let has_speech = rms > SPEECH_RMS_THRESHOLD;
+
+        self.segment.extend_from_slice(frame);
+
+        if !self.is_speech {
+            if has_speech {
+                self.is_speech = true;
+                self.silence_samples = 0;
+                events.push(SegmenterEvent::StartOfSpeech {
+                    sample: self.segment_end_sample() - FRAME_SAMPLES,
+                });
+            } else if self.segment.len() > PREROLL_SAMPLES {
+                let excess = self.segment.len() - PREROLL_SAMPLES;
+                self.segment.drain(..excess);
+                self.segment_start_sample += excess;
+            }
+            return;
+        }
+
+        if has_speech {
+            self.silence_samples = 0;
+        } else {
+            self.silence_samples += FRAME_SAMPLES;
+        }
+
+        if self.silence_samples >= END_OF_SPEECH_SAMPLES {
+            self.end_segment(events);
+        } else if self.segment.len() >= MAX_SEGMENT_SAMPLES {
+            // the speech continues in the next segment
+            let start_sample = self.segment_start_sample;
+            self.segment_start_sample = self.segment_end_sample();
+            events.push(SegmenterEvent::Segment {
+                start_sample,
+                samples: std::mem::take(&mut self.segment),
+            });
+        }
+    }
+
+    fn end_segment(&mut self, events: &mut Vec<SegmenterEvent>) {
+        let start_sample = self.segment_start_sample;
+        let end_sample = self.segment_end_sample();
+
+        events.push(SegmenterEvent::EndOfSpeech {
+            sample: end_sample - self.silence_samples.min(end_sample),
+        });
+        events.push(SegmenterEvent::Segment {
+            start_sample,
+            samples: std::mem::take(&mut self.segment),
+        });
+
+        self.segment_start_sample = end_sample;
+        self.is_speech = false;
+        self.silence_samples = 0;
+    }
+
+    fn segment_end_sample(&self) -> usize {
+        self.segment_start_sample + self.segment.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn silence(samples: usize) -> Vec<f32> {
+        vec![0f32; samples]
+    }
+
+    fn speech(samples: usize) -> Vec<f32> {
+        (0..samples)
+            .map(|i| if i % 2 == 0 { 0.1f32 } else { -0.1f32 })
+            .collect()
+    }
+
+    /// Returns events as `(name, sample, segment length)` to make the assertions short.
+    fn describe(events: &[SegmenterEvent]) -> Vec<(&'static str, usize, usize)> {
+        events
+            .iter()
+            .map(|event| match event {
+                SegmenterEvent::StartOfSpeech { sample } => ("start", *sample, 0),
+                SegmenterEvent::EndOfSpeech { sample } => ("end", *sample, 0),
+                SegmenterEvent::Segment {
+                    start_sample,
+                    samples,
+                } => ("segment", *start_sample, samples.len()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn silence_produces_no_events() {
+        let mut segmenter = SpeechSegmenter::new();
+
+        assert!(segmenter.push(&silence(16000)).is_empty());
+        assert!(segmenter.flush().is_empty());
+    }
+
+    #[test]
+    fn utterance_keeps_preroll_and_ends_after_pause() {
+        let mut segmenter = SpeechSegmenter::new();
+
+        let mut audio = silence(16000);
+        audio.extend(speech(8000));
+        audio.extend(silence(END_OF_SPEECH_SAMPLES));
+
+        assert_eq!(
+            describe(&segmenter.push(&audio)),
+            vec![
+                ("start", 16000, 0),
+                ("end", 24000, 0),
+                (
+                    "segment",
+                    16000 - PREROLL_SAMPLES,
+                    PREROLL_SAMPLES + 8000 + END_OF_SPEECH_SAMPLES
+                ),
+            ]
+        );
+        assert!(segmenter.flush().is_empty());
+    }
+
+    #[test]
+    fn events_do_not_depend_on_buffer_split() {
+        let mut audio = silence(8000);
+        audio.extend(speech(4160));
+        audio.extend(silence(END_OF_SPEECH_SAMPLES));
+        audio.extend(speech(3000));
+
+        let mut segmenter = SpeechSegmenter::new();
+        let mut expected = segmenter.push(&audio);
+        expected.extend(segmenter.flush());
+
+        let mut segmenter = SpeechSegmenter::new();
+        let mut events = Vec::new();
+        for chunk in audio.chunks(123) {
+            events.extend(segmenter.push(chunk));
+        }
+        events.extend(segmenter.flush());
+
+        assert_eq!(describe(&events), describe(&expected));
+        assert_eq!(describe(&events).len(), 6);
+    }
+
+    #[test]
+    fn flush_returns_unfinished_utterance_with_pending_audio() {
+        let mut segmenter = SpeechSegmenter::new();
+
+        let events = segmenter.push(&speech(1000));
+        assert_eq!(describe(&events), vec![("start", 0, 0)]);
+
+        // 1000 - 3 * 320 = 40 samples are waiting for a full frame
+        assert_eq!(
+            describe(&segmenter.flush()),
+            vec![("end", 1000, 0), ("segment", 0, 1000)]
+        );
+
+        // the segmenter starts from the beginning after flush
+        let events = segmenter.push(&speech(FRAME_SAMPLES));
+        assert_eq!(describe(&events), vec![("start", 0, 0)]);
+    }
+
+    #[test]
+    fn long_speech_is_split_into_whisper_windows() {
+        let mut segmenter = SpeechSegmenter::new();
+
+        let events = segmenter.push(&speech(MAX_SEGMENT_SAMPLES + 16000));
+        assert_eq!(
+            describe(&events),
+            vec![("start", 0, 0), ("segment", 0, MAX_SEGMENT_SAMPLES)]
+        );
+
+        assert_eq!(
+            describe(&segmenter.flush()),
+            vec![
+                ("end", MAX_SEGMENT_SAMPLES + 16000, 0),
+                ("segment", MAX_SEGMENT_SAMPLES, 16000)
+            ]
+        );
+    }
+}
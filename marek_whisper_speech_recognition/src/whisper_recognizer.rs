@@ -0,0 +1,475 @@
+use std::{
+    ffi::c_int,
+    path::{Path, PathBuf},
+    thread::{self, JoinHandle},
+};
+
+use async_trait::async_trait;
+use futures::channel::oneshot;
+use marek_speech_recognition_api::{
+    event_channel, AudioBuffer, AudioConverter, AudioFormat, AudioLevelMeter, EndpointReason,
+    EventChannelOptions, EventFilter, EventReceiver, EventSender, RecognitionEvent,
+    RecognitionMode, Recognizer, RecognizerInfo, RecognizerState, SpeechError, SpeechResult, Word,
+};
+use whisper_rs::{
+    FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperState,
+    WhisperTokenId,
+};
+
+use crate::speech_segmenter::{SegmenterEvent, SpeechSegmenter};
+
+/// Whisper models work only with 16 kHz audio.
+const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+pub struct WhisperRecognizer {
+    info: RecognizerInfo,
+    context: WhisperContext,
+    converter: AudioConverter,
+    language: String,
+    threads: usize,
+    sender: EventSender,
+    state: RecognizerState,
+    whisper_thread_sender: Option<std::sync::mpsc::Sender<WhisperRecognizerEvent>>,
+    whisper_thread_handle: Option<JoinHandle<()>>,
+}
+
+impl WhisperRecognizer {
+    pub(crate) fn new(
+        model_path: &Path,
+        audio_format: AudioFormat,
+        language: &str,
+        recognition_mode: RecognitionMode,
+        event_channel_options: EventChannelOptions,
+        threads: usize,
+    ) -> SpeechResult<(Self, EventReceiver)> {
+        if recognition_mode != RecognitionMode::Speech {
+            return Err(SpeechError::RecognitionModeNotSupported(format!(
+                "{:?}",
+                recognition_mode
+            )));
+        }
+
+        let (sender, receiver) = event_channel(event_channel_options);
+
+        let mut context_parameters = WhisperContextParameters::default();
+        context_parameters.use_gpu(false);
+        let context = WhisperContext::new_with_params(model_path, context_parameters)
+            .map_err(|_| SpeechError::LanguageFolderError(PathBuf::from(model_path)))?;
+
+        let converter =
+            AudioConverter::new(audio_format, AudioFormat::mono_f32(WHISPER_SAMPLE_RATE))?;
+
+        Ok((
+            WhisperRecognizer {
+                info: RecognizerInfo {
+                    name: "Whisper".to_string(),
+                    is_realtime_only: false,
+                    has_punctuation: true,
+                    supports_command_updates: false,
+                },
+                context,
+                converter,
+                language: to_whisper_language(language),
+                threads,
+                sender,
+                state: RecognizerState::Created,
+                whisper_thread_sender: None,
+                whisper_thread_handle: None,
+            },
+            receiver,
+        ))
+    }
+
+    /// Reports that the Whisper thread is gone. The thread cannot be used anymore
+    /// and the recognizer needs to be stopped and started again.
+    fn worker_died(&mut self) -> SpeechError {
+        self.whisper_thread_sender = None;
+        let _ = self
+            .sender
+            .send(RecognitionEvent::Error(SpeechError::WorkerDied));
+        SpeechError::WorkerDied
+    }
+
+    /// Sends `Stop` or `Abort` to the Whisper thread and waits for it to finish.
+    fn stop_thread(&mut self, message: WhisperRecognizerEvent) -> SpeechResult {
+        self.state = RecognizerState::Stopping;
+
+        let handle = match self.whisper_thread_handle.take() {
+            Some(handle) => handle,
+            None => {
+                self.state = RecognizerState::Stopped;
+                return Ok(());
+            }
+        };
+
+        // the thread may be already dead, it is detected by join below
+        if let Some(sender) = self.whisper_thread_sender.take() {
+            let _ = sender.send(message);
+        }
+
+        let is_joined = handle.join().is_ok();
+        self.state = RecognizerState::Stopped;
+
+        if !is_joined {
+            return Err(self.worker_died());
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for WhisperRecognizer {
+    fn drop(&mut self) {
+        // end the recognition like `abort` does, so the thread doesn't outlive the recognizer
+        // and doesn't decode the buffered audio when nobody can receive the results
+        if let Some(sender) = self.whisper_thread_sender.take() {
+            let _ = sender.send(WhisperRecognizerEvent::Abort);
+        }
+        if let Some(handle) = self.whisper_thread_handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+enum WhisperRecognizerEvent {
+    Write(Vec<f32>, oneshot::Sender<SpeechResult>),
+    Stop,
+
+    /// Stops without processing the remaining audio.
+    Abort,
+}
+
+#[async_trait]
+impl Recognizer for WhisperRecognizer {
+    fn info(&self) -> &RecognizerInfo {
+        &self.info
+    }
+
+    fn state(&self) -> RecognizerState {
+        self.state
+    }
+
+    fn subscribe(&self, filter: EventFilter, options: EventChannelOptions) -> EventReceiver {
+        self.sender.subscribe(filter, options)
+    }
+
+    async fn start(&mut self) -> SpeechResult {
+        self.state.check_start()?;
+
+        let state = self
+            .context
+            .create_state()
+            .map_err(|err| SpeechError::LoadLibraryError(format!("{:?}", err)))?;
+
+        self.converter.reset();
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.whisper_thread_sender = Some(sender);
+
+        let mut worker = WhisperWorker {
+            state,
+            token_eot: self.context.token_eot(),
+            language: self.language.clone(),
+            last_language: None,
+            threads: self.threads,
+            segmenter: SpeechSegmenter::new(),
+            samples_written: 0,
+            // Whisper doesn't report the audio level, so it is measured here
+            audio_level_meter: AudioLevelMeter::new(WHISPER_SAMPLE_RATE),
+            result_sender: self.sender.clone(),
+        };
+
+        self.whisper_thread_handle = Some(thread::spawn(move || {
+            // sending fails only when the receiver is dropped,
+            // in which case nobody is interested in the events anymore
+            let _ = worker.result_sender.send(RecognitionEvent::Start);
+
+            let mut is_aborted = false;
+
+            // the loop ends also when the recognizer is dropped without calling stop
+            while let Ok(event) = receiver.recv() {
+                match event {
+                    WhisperRecognizerEvent::Write(buffer, sender) => {
+                        for event in worker.audio_level_meter.process((&buffer).into()) {
+                            let _ = worker.result_sender.send(event);
+                        }
+
+                        worker.samples_written += buffer.len();
+                        let events = worker.segmenter.push(&buffer);
+                        let _ = sender.send(worker.process(events, EndpointReason::EndOfSpeech));
+                    }
+                    WhisperRecognizerEvent::Stop => break,
+                    WhisperRecognizerEvent::Abort => {
+                        is_aborted = true;
+                        break;
+                    }
+                }
+            }
+
+            if !is_aborted {
+                let events = worker.segmenter.flush();
+                let _ = worker.process(events, EndpointReason::EndOfAudio);
+
+                let _ = worker.result_sender.send(RecognitionEvent::EndOfAudio {
+                    audio_time_usec: Some(to_usec(worker.samples_written)),
+                });
+            }
+
+            let _ = worker.result_sender.send(RecognitionEvent::Stop);
+        }));
+        self.state = RecognizerState::Running;
+
+        Ok(())
+    }
+
+    async fn write(&mut self, buffer: AudioBuffer<'_>) -> SpeechResult {
+        self.state.check_write()?;
+        if self.state == RecognizerState::Paused {
+            return Ok(());
+        }
+
+        let whisper_thread_sender = self
+            .whisper_thread_sender
+            .as_ref()
+            .ok_or(SpeechError::WorkerDied)?;
+        let buffer = self.converter.convert_f32(buffer)?.into_owned();
+
+        let (finish_sender, finish_receiver) = oneshot::channel::<SpeechResult>();
+        let message = WhisperRecognizerEvent::Write(buffer, finish_sender);
+        if whisper_thread_sender.send(message).is_err() {
+            return Err(self.worker_died());
+        }
+
+        match finish_receiver.await {
+            Ok(result) => result,
+            Err(_) => Err(self.worker_died()),
+        }
+    }
+
+    async fn stop(&mut self) -> SpeechResult {
+        self.state.check_stop()?;
+        self.stop_thread(WhisperRecognizerEvent::Stop)
+    }
+
+    async fn abort(&mut self) -> SpeechResult {
+        self.state.check_abort()?;
+        self.stop_thread(WhisperRecognizerEvent::Abort)
+    }
+
+    async fn pause(&mut self) -> SpeechResult {
+        self.state.check_pause()?;
+
+        // the Whisper thread keeps its state and waits for more audio
+        self.state = RecognizerState::Paused;
+        Ok(())
+    }
+
+    async fn resume(&mut self) -> SpeechResult {
+        self.state.check_resume()?;
+        self.state = RecognizerState::Running;
+        Ok(())
+    }
+}
+
+/// State owned by the Whisper thread.
+struct WhisperWorker {
+    state: WhisperState,
+    token_eot: WhisperTokenId,
+    language: String,
+    last_language: Option<String>,
+    threads: usize,
+    segmenter: SpeechSegmenter,
+    samples_written: usize,
+    audio_level_meter: AudioLevelMeter,
+    result_sender: EventSender,
+}
+
+impl WhisperWorker {
+    /// Transcribes the segments. `endpoint_reason` tells why the segments have ended.
+    fn process(
+        &mut self,
+        events: Vec<SegmenterEvent>,
+        endpoint_reason: EndpointReason,
+    ) -> SpeechResult {
+        let mut result = Ok(());
+
+        for event in events {
+            match event {
+                SegmenterEvent::StartOfSpeech { sample } => {
+                    let _ = self.result_sender.send(RecognitionEvent::StartOfSpeech {
+                        audio_time_usec: Some(to_usec(sample)),
+                    });
+                }
+                SegmenterEvent::EndOfSpeech { sample } => {
+                    let _ = self.result_sender.send(RecognitionEvent::EndOfSpeech {
+                        audio_time_usec: Some(to_usec(sample)),
+                    });
+                }
+                SegmenterEvent::Segment {
+                    start_sample,
+                    samples,
+                } => {
+                    if let Err(error) = self.transcribe(start_sample, &samples, endpoint_reason) {
+                        let _ = self
+                            .result_sender
+                            .send(RecognitionEvent::Error(error.clone()));
+                        result = Err(error);
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    fn transcribe(
+        &mut self,
+        start_sample: usize,
+        samples: &[f32],
+        endpoint_reason: EndpointReason,
+    ) -> SpeechResult {
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_n_threads(self.threads as c_int);
+        params.set_language(Some(&self.language));
+        params.set_token_timestamps(true);
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+
+        self.state
+            .full(params, samples)
+            .map_err(|err| SpeechError::DecodingFailed(format!("{:?}", err)))?;
+
+        self.send_language();
+
+        let offset_usec = to_usec(start_sample);
+        let mut results = Vec::new();
+
+        // Whisper splits longer speech into segments (usually sentences), each one is reported
+        // as a separate final result with its own times
+        for segment in self.state.as_iter() {
+            let text = segment
+                .to_str_lossy()
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+            if text.is_empty() {
+                continue;
+            }
+
+            let mut words: Vec<Word> = Vec::new();
+            let mut word_probabilities: Vec<f32> = Vec::new();
+
+            for i in 0..segment.n_tokens() {
+                let token = match segment.get_token(i) {
+                    Some(token) => token,
+                    None => continue,
+                };
+
+                // skip special tokens like timestamps or end of text
+                if token.token_id() >= self.token_eot {
+                    continue;
+                }
+
+                let token_text = token.to_str_lossy().unwrap_or_default();
+                let data = token.token_data();
+
+                // token times are in centiseconds
+                let start_time_usec = offset_usec + data.t0.max(0) as u64 * 10000u64;
+                let end_time_usec = offset_usec + data.t1.max(0) as u64 * 10000u64;
+
+                // tokens are parts of words, a new word starts with a space
+                match words.last_mut() {
+                    Some(word) if !token_text.starts_with(' ') => {
+                        word.word.push_str(&token_text);
+                        word.end_time_usec = end_time_usec;
+                        word_probabilities.push(token.token_probability());
+                    }
+                    _ => {
+                        finish_word(&mut words, &mut word_probabilities);
+                        words.push(Word {
+                            conf: 0f32,
+                            start_time_usec,
+                            end_time_usec,
+                            word: token_text.trim_start().to_string(),
+                        });
+                        word_probabilities.push(token.token_probability());
+                    }
+                }
+            }
+            finish_word(&mut words, &mut word_probabilities);
+
+            // segment times are in centiseconds
+            let audio_start_time_usec =
+                offset_usec + segment.start_timestamp().max(0) as u64 * 10000u64;
+            let audio_end_time_usec =
+                offset_usec + segment.end_timestamp().max(0) as u64 * 10000u64;
+
+            results.push((text, audio_start_time_usec, audio_end_time_usec, words));
+        }
+
+        let results_len = results.len();
+        for (i, (text, audio_start_time_usec, audio_end_time_usec, words)) in
+            results.into_iter().enumerate()
+        {
+            // only the last segment ends where the audio has been split
+            let endpoint_reason = if i + 1 == results_len {
+                endpoint_reason
+            } else {
+                EndpointReason::EndOfUtterance
+            };
+
+            let _ = self.result_sender.send(RecognitionEvent::Recognition {
+                text,
+                is_final: true,
+                audio_start_time_usec: Some(audio_start_time_usec),
+                audio_end_time_usec: Some(audio_end_time_usec),
+                words: Some(words),
+                endpoint_reason: Some(endpoint_reason),
+                alternatives: Vec::new(),
+                speaker_vector: None,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn send_language(&mut self) {
+        let language = whisper_rs::get_lang_str(self.state.full_lang_id_from_state())
+            .map(|language| language.to_string());
+
+        if language.is_some() && language != self.last_language {
+            self.last_language = language.clone();
+            if let Some(id) = language {
+                let _ = self.result_sender.send(RecognitionEvent::Language {
+                    id,
+                    confidence_level: None,
+                });
+            }
+        }
+    }
+}
+
+/// Sets the confidence of the last word to the mean probability of its tokens.
+fn finish_word(words: &mut [Word], word_probabilities: &mut Vec<f32>) {
+    if let Some(word) = words.last_mut() {
+        if !word_probabilities.is_empty() {
+            word.conf = word_probabilities.iter().sum::<f32>() / word_probabilities.len() as f32;
+        }
+    }
+    word_probabilities.clear();
+}
+
+/// Converts e.g. "en-US" to "en". "auto" enables language detection.
+fn to_whisper_language(language: &str) -> String {
+    language
+        .split(['-', '_'])
+        .next()
+        .unwrap_or(language)
+        .to_lowercase()
+}
+
+fn to_usec(samples: usize) -> u64 {
+    samples as u64 * 1000000u64 / WHISPER_SAMPLE_RATE as u64
+}
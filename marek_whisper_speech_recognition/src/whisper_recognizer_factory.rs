@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+use std::thread;
+
+use marek_speech_recognition_api::{
+    unique_languages, EventReceiver, Recognizer, RecognizerFactory, RecognizerOptions, SpeechError,
+    SpeechResult,
+};
+
+use crate::WhisperRecognizer;
+
+pub struct WhisperRecognizerFactory {
+    models: Vec<WhisperModelInfo>,
+    threads: usize,
+}
+
+pub struct WhisperModelInfo {
+    /// Language of the recognizer, e.g. "en-US".
+    /// Multilingual models can be registered also as "auto" to detect the language.
+    pub language: String,
+
+    /// Path to the `ggml` model file.
+    pub file: PathBuf,
+}
+
+impl WhisperRecognizerFactory {
+    pub fn new(models: Vec<WhisperModelInfo>) -> SpeechResult<Self> {
+        // without a logging backend the hooks would drop whisper.cpp logs (including errors),
+        // so they are installed only when logs can be forwarded, otherwise they go to stderr
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        whisper_rs::install_logging_hooks();
+
+        Ok(Self {
+            models,
+            threads: thread::available_parallelism()
+                .map(|threads| threads.get())
+                .unwrap_or(1)
+                .min(8),
+        })
+    }
+
+    /// Sets the number of CPU threads used for a single inference.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+}
+
+impl RecognizerFactory for WhisperRecognizerFactory {
+    fn create_recognizer(
+        &mut self,
+        options: RecognizerOptions,
+    ) -> SpeechResult<(Box<dyn Recognizer + Send>, EventReceiver)> {
+        let model_path = self
+            .models
+            .iter()
+            .filter(|el| el.language == options.language)
+            .map(|el| el.file.clone())
+            .next()
+            .ok_or(SpeechError::NoLanguageFound(options.language.clone()))?;
+
+        let (recognizer, receiver) = WhisperRecognizer::new(
+            &model_path,
+            options.audio_format,
+            &options.language,
+            options.mode,
+            options.event_channel,
+            self.threads,
+        )?;
+
+        Ok((Box::new(recognizer), receiver))
+    }
+
+    fn available_languages(&self) -> SpeechResult<Vec<String>> {
+        Ok(unique_languages(
+            self.models.iter().map(|model| &model.language),
+        ))
+    }
+}
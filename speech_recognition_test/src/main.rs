@@ -1,10 +1,14 @@
 use byteorder::{ByteOrder, LittleEndian};
 use futures_util::stream::StreamExt;
 use marek_google_speech_recognition::GoogleRecognizerFactory;
+use marek_sherpa_speech_recognition::{
+    SherpaModel, SherpaModelInfo, SherpaRecognizerFactory, SherpaTransducerFiles,
+};
 use marek_speech_recognition_api::{
     RecognitionEvent, RecognizerFactory, RecognizerOptions, SpeechResult,
 };
-use marek_vosk_speech_recognition::{VoskModelInfo, VoskRecognizerFactory};
+use marek_vosk_speech_recognition::{VoskModelSize, VoskRecognizerFactory};
+use marek_whisper_speech_recognition::{WhisperModelInfo, WhisperRecognizerFactory};
 use std::error::Error;
 use std::io::Write;
 use std::path::PathBuf;
@@ -35,7 +39,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let step_size = 1024;
     for pos in (0..audio_raw_data.len()).step_by(step_size) {
         recognizer
-            .write(&audio_raw_data[pos..(pos + step_size).min(audio_raw_data.len())])
+            .write(audio_raw_data[pos..(pos + step_size).min(audio_raw_data.len())].into())
             .await?;
     }
 
@@ -48,7 +52,7 @@ fn create_recognizer_factory() -> SpeechResult<Box<dyn RecognizerFactory>> {
     let mut answer = String::new();
 
     loop {
-        print!("Choose recognizer [google, vosk]: ");
+        print!("Choose recognizer [google, vosk, whisper, sherpa]: ");
         std::io::stdout().flush().unwrap();
         std::io::stdin().read_line(&mut answer).unwrap();
         answer = answer
@@ -63,12 +67,38 @@ fn create_recognizer_factory() -> SpeechResult<Box<dyn RecognizerFactory>> {
                 "./SODALanguagePacks",
             )?));
         } else if answer == "vosk" {
-            return Ok(Box::new(VoskRecognizerFactory::new(vec![VoskModelInfo {
-                language: "en-US".to_string(),
-                folder: PathBuf::from(
-                    "/home/marek/Ext/Src/language/vosk_models/vosk-model-small-en-us-0.15",
-                ),
-            }])?));
+            return Ok(Box::new(VoskRecognizerFactory::from_models_folder(
+                "/usr/local/share/vosk-models",
+                VoskModelSize::Small,
+            )?));
+        } else if answer == "whisper" {
+            return Ok(Box::new(WhisperRecognizerFactory::new(vec![
+                WhisperModelInfo {
+                    language: "en-US".to_string(),
+                    file: PathBuf::from("/usr/local/share/whisper-models/ggml-base.en.bin"),
+                },
+            ])?));
+        } else if answer == "sherpa" {
+            let folder = PathBuf::from(
+                "/usr/local/share/sherpa-models/sherpa-onnx-nemo-parakeet-tdt-0.6b-v3-int8",
+            );
+            return Ok(Box::new(SherpaRecognizerFactory::new(vec![
+                SherpaModelInfo {
+                    language: "en-US".to_string(),
+                    model: SherpaModel::Offline {
+                        files: SherpaTransducerFiles {
+                            encoder: folder.join("encoder.int8.onnx"),
+                            decoder: folder.join("decoder.int8.onnx"),
+                            joiner: folder.join("joiner.int8.onnx"),
+                            tokens: folder.join("tokens.txt"),
+                        },
+                        model_type: "nemo_transducer".to_string(),
+                        vad_model: PathBuf::from("/usr/local/share/sherpa-models/silero_vad.onnx"),
+                        feature_dim: 128,
+                    },
+                    has_punctuation: true,
+                },
+            ])?));
         }
     }
 }